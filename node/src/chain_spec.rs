@@ -15,13 +15,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use minimal_template_runtime::{BalancesConfig, SudoConfig, WASM_BINARY};
+use minimal_template_runtime::{
+	BalancesConfig, GrandpaConfig, RoundRobinConfig, SudoConfig, WASM_BINARY,
+};
 use polkadot_sdk::{
+	sc_consensus_grandpa::AuthorityId as GrandpaId,
 	sc_service::{ChainType, Properties},
-	sp_keyring::AccountKeyring,
+	sp_keyring::{AccountKeyring, Ed25519Keyring},
 	*,
 };
 use serde_json::{json, Value};
+use sp_consensus_round_robin::{ml_dsa, sr25519, AuthorityId, SealScheme};
+use sp_pq_crypto::MultiSigner;
 
 /// This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec;
@@ -38,21 +43,89 @@ pub fn development_config() -> Result<ChainSpec, String> {
 		.with_name("Development")
 		.with_id("dev")
 		.with_chain_type(ChainType::Development)
-		.with_genesis_config_patch(testnet_genesis())
+		.with_genesis_config_patch(testnet_genesis(SealScheme::Sr25519))
+		.with_properties(props())
+		.build())
+}
+
+/// Development chain whose round-robin authorities seal blocks with ML-DSA-65, and whose
+/// development accounts additionally have ML-DSA-65 counterparts.
+pub fn development_pq_config() -> Result<ChainSpec, String> {
+	Ok(ChainSpec::builder(WASM_BINARY.expect("Development wasm not available"), Default::default())
+		.with_name("Development (post-quantum)")
+		.with_id("dev_pq")
+		.with_chain_type(ChainType::Development)
+		.with_genesis_config_patch(testnet_genesis(SealScheme::MlDsa65))
 		.with_properties(props())
 		.build())
 }
 
+/// The round-robin authority key of a development account under `scheme`.
+///
+/// ML-DSA-65 keys are derived from the same `//Alice`-style URIs that `--alice` and friends
+/// insert into the keystore.
+fn authority_key(account: AccountKeyring, scheme: SealScheme) -> AuthorityId {
+	match scheme {
+		SealScheme::Sr25519 => sr25519::AuthorityId::from(account.public()).into(),
+		SealScheme::MlDsa65 => ml_dsa_public(account).into(),
+	}
+}
+
+/// The ML-DSA-65 key derived from a development account's `//Alice`-style URI.
+fn ml_dsa_public(account: AccountKeyring) -> ml_dsa::AuthorityId {
+	ml_dsa::AuthorityPair::from_string(&format!("//{}", account))
+		.expect("static values are valid; qed")
+		.public()
+}
+
+/// How long after a block a development validator may author in place of a scheduled one that
+/// missed its turn, in milliseconds.
+const MISSED_TURN_TIMEOUT: u64 = 3_000;
+
+/// The block time of development validators, that of `--block-time` by default, in milliseconds.
+const BLOCK_TIME: u64 = 1_000;
+
 /// Configure initial storage state for FRAME pallets.
-fn testnet_genesis() -> Value {
+fn testnet_genesis(seal_scheme: SealScheme) -> Value {
 	use minimal_template_runtime::interface::{Balance, MinimumBalance};
-	use polkadot_sdk::polkadot_sdk_frame::traits::Get;
+	use polkadot_sdk::{polkadot_sdk_frame::traits::Get, sp_runtime::traits::IdentifyAccount};
 	let endowment = <MinimumBalance as Get<Balance>>::get().max(1) * 1000;
-	let balances = AccountKeyring::iter()
+	let mut balances = AccountKeyring::iter()
 		.map(|a| (a.to_account_id(), endowment))
 		.collect::<Vec<_>>();
+	if seal_scheme == SealScheme::MlDsa65 {
+		balances.extend(
+			AccountKeyring::iter()
+				.map(|a| (MultiSigner::from(ml_dsa_public(a)).into_account(), endowment)),
+		);
+	}
+	let validators = [
+		(AccountKeyring::Alice, Ed25519Keyring::Alice),
+		(AccountKeyring::Bob, Ed25519Keyring::Bob),
+		(AccountKeyring::Charlie, Ed25519Keyring::Charlie),
+	];
+	let authorities = validators
+		.iter()
+		.map(|(a, _)| authority_key(*a, seal_scheme))
+		.collect::<Vec<_>>();
+	// The GRANDPA voters follow the finality keys of the round-robin authorities from here on
+	let finality_keys = authorities
+		.iter()
+		.cloned()
+		.zip(validators.iter().map(|(_, a)| GrandpaId::from(a.public())))
+		.collect::<Vec<_>>();
+	let grandpa_authorities =
+		finality_keys.iter().map(|(_, key)| (key.clone(), 1)).collect::<Vec<_>>();
 	json!({
 		"balances": BalancesConfig { balances },
 		"sudo": SudoConfig { key: Some(AccountKeyring::Alice.to_account_id()) },
+		"roundRobin": RoundRobinConfig {
+			authorities,
+			finality_keys,
+			missed_turn_timeout: Some(MISSED_TURN_TIMEOUT),
+			block_time: Some(BLOCK_TIME),
+			..Default::default()
+		},
+		"grandpa": GrandpaConfig { authorities: grandpa_authorities, ..Default::default() },
 	})
 }
@@ -15,59 +15,100 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::consensus::{parse_engine, ConsensusEngine};
+use minimal_template_runtime::interface::AccountId;
 use polkadot_sdk::{sc_cli::RunCmd, *};
-
-#[derive(Debug, Clone)]
-pub enum Consensus {
-	ManualSeal(u64),
-	InstantSeal,
-	RoundRobin {
-		validator_id: u32,
-		total_validators: u32,
-	},
-}
-
-impl std::str::FromStr for Consensus {
-	type Err = String;
-
-	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		Ok(if s == "instant-seal" {
-			Consensus::InstantSeal
-		} else if let Some(block_time) = s.strip_prefix("manual-seal-") {
-			Consensus::ManualSeal(block_time.parse().map_err(|_| "invalid block time")?)
-		} else if let Some(params) = s.strip_prefix("round-robin-") {
-			let parts: Vec<&str> = params.split('-').collect();
-			if parts.len() != 2 {
-				return Err("round-robin requires validator-id and total-validators".into());
-			}
-			Consensus::RoundRobin {
-				validator_id: parts[0].parse().map_err(|_| "invalid validator id")?,
-				total_validators: parts[1].parse().map_err(|_| "invalid total validators")?,
-			}
-		} else {
-			return Err("incorrect consensus identifier".into());
-		})
-	}
-}
+use std::path::PathBuf;
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
 	#[command(subcommand)]
 	pub subcommand: Option<Subcommand>,
 
-	#[clap(long, default_value = "manual-seal-3000")]
-	pub consensus: Consensus,
+	/// The consensus engine: `manual-seal-<ms>`, `instant-seal`, `round-robin`, `pow` or
+	/// `pow-<threads>` [default: manual-seal-3000]. `round-robin-<id>-<total>` also checks this
+	/// node's position in the on-chain authority set at startup.
+	#[clap(long)]
+	pub consensus: Option<String>,
 
 	#[clap(flatten)]
 	pub run: RunCmd,
 
-	/// Validator ID for round-robin consensus
+	/// Validator ID for round-robin consensus. Turns are given by the position of this node's key
+	/// in the on-chain authority set; a mismatching ID is an error at startup.
 	#[arg(long)]
 	pub validator_id: Option<u32>,
 
-	/// Total number of validators in the network
+	/// Total number of validators in the network, as expected in the on-chain authority set
 	#[arg(long)]
 	pub total_validators: Option<u32>,
+
+	/// Round-robin block time in milliseconds: how long validators wait after a block arrives
+	/// before building on it [default: 1000]
+	#[arg(long)]
+	pub block_time: Option<u64>,
+
+	/// How long a round-robin validator may spend adding transactions to a block, in milliseconds
+	/// [default: half the block time]
+	#[arg(long)]
+	pub proposal_deadline: Option<u64>,
+
+	/// Append round-robin consensus events (turns, authored and imported blocks) to this file as
+	/// JSON lines
+	#[arg(long)]
+	pub consensus_events: Option<PathBuf>,
+
+	/// How far ahead of the local clock, in milliseconds, the timestamp of an imported round-robin
+	/// block may be, at most 30000 [default: 10000]
+	#[arg(long)]
+	pub max_timestamp_drift: Option<u64>,
+
+	/// Account paid the rewards of blocks mined by this node under proof-of-work
+	#[arg(long)]
+	pub miner_account: Option<AccountId>,
+}
+
+impl Cli {
+	/// The consensus engine this node runs, as given by `--consensus`.
+	///
+	/// `--validator-id` and `--total-validators` are a shorthand for
+	/// `--consensus round-robin-<id>-<total>` and must agree with `--consensus` if both are given.
+	/// Flags belonging to another engine than the selected one are rejected.
+	pub fn consensus(&self) -> Result<Box<dyn ConsensusEngine>, String> {
+		let id = match (&self.consensus, self.validator_id, self.total_validators) {
+			(Some(id), None, None) | (Some(id), Some(_), Some(_)) => id.clone(),
+			(None, None, None) => "manual-seal-3000".into(),
+			(None, Some(validator_id), Some(total_validators)) =>
+				format!("round-robin-{}-{}", validator_id, total_validators),
+			(_, _, _) =>
+				return Err("--validator-id and --total-validators must be given together".into()),
+		};
+		let engine = parse_engine(&id, self)?;
+
+		if self.validator_id.is_some() && engine.name() != "round-robin" {
+			return Err("--validator-id and --total-validators conflict with --consensus; \
+				use --consensus round-robin-<id>-<total> alone"
+				.into());
+		}
+		if (self.block_time.is_some() || self.proposal_deadline.is_some()) &&
+			engine.name() != "round-robin"
+		{
+			return Err("--block-time and --proposal-deadline require \
+				--consensus round-robin"
+				.into());
+		}
+		if self.consensus_events.is_some() && engine.name() != "round-robin" {
+			return Err("--consensus-events requires --consensus round-robin".into());
+		}
+		if self.max_timestamp_drift.is_some() && engine.name() != "round-robin" {
+			return Err("--max-timestamp-drift requires --consensus round-robin".into());
+		}
+		if self.miner_account.is_some() && engine.name() != "pow" {
+			return Err("--miner-account requires --consensus pow".into());
+		}
+
+		Ok(engine)
+	}
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -99,4 +140,34 @@ pub enum Subcommand {
 
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
+
+	/// Insert a post-quantum (ML-DSA-65) round-robin authority key into the keystore.
+	InsertPqKey(InsertPqKeyCmd),
+}
+
+/// The `insert-pq-key` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct InsertPqKeyCmd {
+	/// The secret key URI. A `0x`-prefixed 32-byte hex seed is used as is; any other string is
+	/// hashed into a seed.
+	#[arg(long)]
+	pub suri: String,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore_params: sc_cli::KeystoreParams,
+}
+
+impl sc_cli::CliConfiguration for InsertPqKeyCmd {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+
+	fn keystore_params(&self) -> Option<&sc_cli::KeystoreParams> {
+		Some(&self.keystore_params)
+	}
 }
@@ -18,7 +18,8 @@
 use crate::{
 	chain_spec,
 	cli::{Cli, Subcommand},
-	service,
+	consensus::PqKeystore,
+	service::{self, Other},
 };
 use polkadot_sdk::{sc_cli::SubstrateCli, sc_service::PartialComponents, *};
 
@@ -50,6 +51,7 @@ impl SubstrateCli for Cli {
 	fn load_spec(&self, id: &str) -> Result<Box<dyn sc_service::ChainSpec>, String> {
 		Ok(match id {
 			"dev" => Box::new(chain_spec::development_config()?),
+			"dev-pq" => Box::new(chain_spec::development_pq_config()?),
 			path =>
 				Box::new(chain_spec::ChainSpec::from_json_file(std::path::PathBuf::from(path))?),
 		})
@@ -59,6 +61,7 @@ impl SubstrateCli for Cli {
 /// Parse and run command line arguments
 pub fn run() -> sc_cli::Result<()> {
 	let cli = Cli::from_args();
+	let engine = cli.consensus().map_err(sc_cli::Error::Input)?;
 
 	match &cli.subcommand {
 		Some(Subcommand::Key(cmd)) => cmd.run(&cli),
@@ -70,21 +73,23 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, import_queue, .. } =
-					service::new_partial(&config)?;
+					service::new_partial(&config, &*engine)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		},
 		Some(Subcommand::ExportBlocks(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
-				let PartialComponents { client, task_manager, .. } = service::new_partial(&config)?;
+				let PartialComponents { client, task_manager, .. } =
+					service::new_partial(&config, &*engine)?;
 				Ok((cmd.run(client, config.database), task_manager))
 			})
 		},
 		Some(Subcommand::ExportState(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
-				let PartialComponents { client, task_manager, .. } = service::new_partial(&config)?;
+				let PartialComponents { client, task_manager, .. } =
+					service::new_partial(&config, &*engine)?;
 				Ok((cmd.run(client, config.chain_spec), task_manager))
 			})
 		},
@@ -92,7 +97,7 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, import_queue, .. } =
-					service::new_partial(&config)?;
+					service::new_partial(&config, &*engine)?;
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		},
@@ -103,9 +108,24 @@ pub fn run() -> sc_cli::Result<()> {
 		Some(Subcommand::Revert(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
-				let PartialComponents { client, task_manager, backend, .. } =
-					service::new_partial(&config)?;
-				Ok((cmd.run(client, backend, None), task_manager))
+				let PartialComponents {
+					client,
+					task_manager,
+					backend,
+					other: Other { grandpa, .. },
+					..
+				} = service::new_partial(&config, &*engine)?;
+				// GRANDPA keeps its authority set in auxiliary storage, which must be reverted too
+				let revert = if grandpa.is_some() {
+					let aux_revert = Box::new(|client, _, blocks| {
+						sc_consensus_grandpa::revert(client, blocks)?;
+						Ok(())
+					});
+					cmd.run(client, backend, Some(aux_revert))
+				} else {
+					cmd.run(client, backend, None)
+				};
+				Ok((revert, task_manager))
 			})
 		},
 		Some(Subcommand::ChainInfo(cmd)) => {
@@ -114,18 +134,31 @@ pub fn run() -> sc_cli::Result<()> {
 				cmd.run::<minimal_template_runtime::interface::OpaqueBlock>(&config)
 			})
 		},
+		Some(Subcommand::InsertPqKey(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let keystore = PqKeystore::open(config.keystore.path())?;
+				let public = keystore.insert(&cmd.suri)?;
+				let public = serde_json::to_string(&public).map_err(std::io::Error::from)?;
+				println!("Inserted ML-DSA-65 key {}", public);
+				Ok(())
+			})
+		},
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
 			runner.run_node_until_exit(|config| async move {
-				match config.network.network_backend {
+				let (mut task_manager, authoring) = match config.network.network_backend {
 					sc_network::config::NetworkBackendType::Libp2p =>
-						service::new_full::<sc_network::NetworkWorker<_, _>>(config, &cli)
-							.map_err(sc_cli::Error::Service),
-					sc_network::config::NetworkBackendType::Litep2p => service::new_full::<
-						sc_network::Litep2pNetworkBackend,
-					>(config, &cli)
-					.map_err(sc_cli::Error::Service),
+						service::new_full::<sc_network::NetworkWorker<_, _>>(config, engine),
+					sc_network::config::NetworkBackendType::Litep2p =>
+						service::new_full::<sc_network::Litep2pNetworkBackend>(config, engine),
+				}
+				.map_err(sc_cli::Error::Service)?;
+				// Stop authoring along with the node's tasks on shutdown
+				if let Some(authoring) = authoring {
+					task_manager.keep_alive(authoring.stop_on_drop());
 				}
+				Ok(task_manager)
 			})
 		},
 	}
@@ -1,101 +1,99 @@
 use super::block_production::BlockProducer;
+use async_trait::async_trait;
 use polkadot_sdk::{
-    sc_client_api::{Backend, BlockBackend, HeaderBackend},
-    sp_api::{Core, ProvideRuntimeApi},
-    sp_block_builder::BlockBuilder,
-    sp_consensus::Error as ConsensusError,
-    sp_inherents::{InherentData, InherentDataProvider},
-    sp_runtime::traits::{Block as BlockT, Header as HeaderT},
-    sp_timestamp,
+	sc_client_api::HeaderBackend,
+	sp_consensus::{Environment, Error as ConsensusError, Proposer},
+	sp_inherents::{CreateInherentDataProviders, InherentDataProvider},
+	sp_runtime::{traits::Block as BlockT, Digest},
 };
-use std::{marker::PhantomData, sync::Arc, time::SystemTime};
-use async_trait::async_trait;
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 
-pub struct BasicBlockProducer<Block: BlockT, Client, BE> {
-    client: Arc<Client>,
-    _phantom: PhantomData<(Block, BE)>,
+/// Builds blocks with a proposer `Environment`, such as `sc_basic_authorship::ProposerFactory`,
+/// which fills them with ready transactions from the pool within the block weight and length
+/// limits.
+///
+/// The inherents of every block come from the providers `CIDP` creates for its parent and the
+/// digests the block is built with, as with manual seal: a timestamp provider in the simplest case,
+/// one stamping the block with the slot its pre-digest claims, or a fixed timestamp in tests.
+pub struct BasicBlockProducer<Block: BlockT, Client, E, CIDP> {
+	client: Arc<Client>,
+	env: E,
+	create_inherent_data_providers: CIDP,
+	proposal_deadline: Duration,
+	_phantom: PhantomData<Block>,
 }
 
-impl<Block, Client, BE> BasicBlockProducer<Block, Client, BE>
+impl<Block, Client, E, CIDP> BasicBlockProducer<Block, Client, E, CIDP>
 where
-    Block: BlockT,
-    Block::Header: HeaderT,
-    BE: Backend<Block>,
-    Client: HeaderBackend<Block> + ProvideRuntimeApi<Block> + BlockBackend<Block>,
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+	E: Environment<Block>,
+	CIDP: CreateInherentDataProviders<Block, Digest>,
 {
-    pub fn new(client: Arc<Client>) -> Self {
-        Self {
-            client,
-            _phantom: PhantomData,
-        }
-    }
+	/// Creates a producer proposing with `env` on the inherent data of the providers
+	/// `create_inherent_data_providers` creates, which stops adding transactions to a block once
+	/// `proposal_deadline` has passed.
+	pub fn new(
+		client: Arc<Client>,
+		env: E,
+		create_inherent_data_providers: CIDP,
+		proposal_deadline: Duration,
+	) -> Self {
+		Self {
+			client,
+			env,
+			create_inherent_data_providers,
+			proposal_deadline,
+			_phantom: PhantomData,
+		}
+	}
 }
 
 #[async_trait]
-impl<Block, Client, BE> BlockProducer<Block> for BasicBlockProducer<Block, Client, BE>
+impl<Block, Client, E, CIDP> BlockProducer<Block> for BasicBlockProducer<Block, Client, E, CIDP>
 where
-    Block: BlockT,
-    Client: HeaderBackend<Block> 
-        + ProvideRuntimeApi<Block> 
-        + BlockBackend<Block> 
-        + Send 
-        + Sync,
-    Client::Api: BlockBuilder<Block> + Core<Block>,
-    BE: Backend<Block> + Send + Sync,
+	Block: BlockT,
+	Client: HeaderBackend<Block> + Send + Sync,
+	E: Environment<Block> + Send + Sync,
+	E::Error: Send + Sync,
+	E::Proposer: Proposer<Block> + Send,
+	<E::Proposer as Proposer<Block>>::Error: Send + Sync,
+	CIDP: CreateInherentDataProviders<Block, Digest> + Send + Sync,
 {
-    async fn produce_block(
-        &self,
-        parent_hash: Block::Hash,
-        _parent_number: <<Block as BlockT>::Header as HeaderT>::Number,
-    ) -> Result<Block, ConsensusError> {
-        // Create timestamp for the new block
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        // Create inherent data
-        let mut inherent_data = InherentData::new();
-        let timestamp_data = sp_timestamp::InherentDataProvider::new(timestamp.into());
-        
-        timestamp_data
-            .provide_inherent_data(&mut inherent_data)
-            .await
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?;
-
-        // Get the runtime API
-        let runtime_api = self.client.runtime_api();
+	async fn produce_block(
+		&mut self,
+		parent_hash: Block::Hash,
+		inherent_digests: Digest,
+	) -> Result<Block, ConsensusError> {
+		// Get the parent header
+		let parent_header = self
+			.client
+			.header(parent_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?
+			.ok_or(ConsensusError::ChainLookup("Parent header not found".into()))?;
 
-        // Get the parent header
-        let parent_header = self
-            .client
-            .header(parent_hash)
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?
-            .ok_or(ConsensusError::ChainLookup("Parent header not found".into()))?;
+		// Create inherent data
+		let inherent_data = self
+			.create_inherent_data_providers
+			.create_inherent_data_providers(parent_hash, inherent_digests.clone())
+			.await
+			.map_err(ConsensusError::Other)?
+			.create_inherent_data()
+			.await
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
 
-        // Initialize block with at parent hash
-        runtime_api
-            .initialize_block(parent_hash, &parent_header)
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?;
+		let proposer = self
+			.env
+			.init(&parent_header)
+			.await
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
 
-        // Create inherent extrinsics
-        let inherent_extrinsics = runtime_api
-            .inherent_extrinsics(parent_hash, inherent_data)
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?;
+		// Apply the inherents and as many pool transactions as fit before the deadline
+		let proposal = proposer
+			.propose(inherent_data, inherent_digests, self.proposal_deadline, None)
+			.await
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
 
-        // Add all inherent extrinsics
-        for inherent in inherent_extrinsics.iter() {
-            runtime_api
-                .apply_extrinsic(parent_hash, inherent.clone())
-                .map_err(|e| ConsensusError::Other(Box::new(e)))?;
-        }
-
-        // Finalize the block
-        let header = runtime_api
-            .finalize_block(parent_hash)
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?;
-
-        // Create the block with inherent extrinsics
-        Ok(Block::new(header, inherent_extrinsics))
-    }
-} 
+		Ok(proposal.block)
+	}
+}
@@ -0,0 +1,35 @@
+use futures::{future::BoxFuture, FutureExt};
+use futures_timer::Delay;
+use polkadot_sdk::sp_timestamp::Timestamp;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The time source of round-robin authoring.
+///
+/// Authoring only reads the time and sleeps through this trait, so simulations can run it on a
+/// mocked clock.
+pub trait Clock: Send + Sync {
+	/// The time elapsed since the Unix epoch.
+	fn now(&self) -> Duration;
+
+	/// Completes once `duration` has passed.
+	fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+	/// The current time as a timestamp.
+	fn timestamp(&self) -> Timestamp {
+		Timestamp::new(self.now().as_millis() as u64)
+	}
+}
+
+/// The system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> Duration {
+		SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+	}
+
+	fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+		Delay::new(duration).boxed()
+	}
+}
@@ -0,0 +1,97 @@
+use futures::channel::mpsc;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Whether the authoring task authors blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthoringState {
+	/// Authoring on our turns.
+	#[default]
+	Running,
+	/// Following the chain without authoring, until resumed.
+	Paused,
+	/// Stopped for good: the task has ended or is about to.
+	Stopped,
+}
+
+#[derive(Default)]
+struct Shared {
+	state: Mutex<AuthoringState>,
+	listeners: Mutex<Vec<mpsc::UnboundedSender<AuthoringState>>>,
+}
+
+/// Pauses, resumes and stops an authoring task. Clones control the same task.
+///
+/// A block in progress is always finished: the task only checks its state between blocks.
+#[derive(Clone, Default)]
+pub struct AuthoringHandle {
+	shared: Arc<Shared>,
+}
+
+impl AuthoringHandle {
+	pub fn state(&self) -> AuthoringState {
+		*self.shared.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	/// Stops authoring until resumed. Returns the new state.
+	pub fn pause(&self) -> AuthoringState {
+		self.transition(|state| match state {
+			AuthoringState::Running => AuthoringState::Paused,
+			state => state,
+		})
+	}
+
+	/// Resumes authoring after a pause. A stopped task stays stopped. Returns the new state.
+	pub fn resume(&self) -> AuthoringState {
+		self.transition(|state| match state {
+			AuthoringState::Paused => AuthoringState::Running,
+			state => state,
+		})
+	}
+
+	/// Ends the authoring task.
+	pub fn stop(&self) {
+		self.transition(|_| AuthoringState::Stopped);
+	}
+
+	/// Stops authoring when the returned guard is dropped, such as when the node shuts down.
+	pub fn stop_on_drop(&self) -> StopOnDrop {
+		StopOnDrop(self.clone())
+	}
+
+	/// The states the task is moved to from now on.
+	pub fn state_changes(&self) -> mpsc::UnboundedReceiver<AuthoringState> {
+		let (sender, receiver) = mpsc::unbounded();
+		self.shared
+			.listeners
+			.lock()
+			.unwrap_or_else(|poisoned| poisoned.into_inner())
+			.push(sender);
+		receiver
+	}
+
+	fn transition(&self, next: impl FnOnce(AuthoringState) -> AuthoringState) -> AuthoringState {
+		let mut state = self.shared.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		let previous = *state;
+		*state = next(previous);
+		if *state != previous {
+			let state = *state;
+			self.shared
+				.listeners
+				.lock()
+				.unwrap_or_else(|poisoned| poisoned.into_inner())
+				.retain(|listener| listener.unbounded_send(state).is_ok());
+		}
+		*state
+	}
+}
+
+/// Stops authoring when dropped.
+pub struct StopOnDrop(AuthoringHandle);
+
+impl Drop for StopOnDrop {
+	fn drop(&mut self) {
+		self.0.stop();
+	}
+}
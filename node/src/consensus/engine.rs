@@ -0,0 +1,120 @@
+use super::{AuthoringHandle, InstantSealEngine, ManualSealEngine, PowEngine, RoundRobinEngine};
+use crate::{
+	cli::Cli,
+	service::{FullClient, FullPool, FullSelectChain},
+};
+use jsonrpsee::RpcModule;
+use minimal_template_runtime::interface::OpaqueBlock as Block;
+use polkadot_sdk::{
+	sc_basic_authorship::ProposerFactory,
+	sc_consensus::{BoxBlockImport, BoxJustificationImport, DefaultImportQueue},
+	sc_network_sync::SyncingService,
+	sc_service::{error::Error as ServiceError, Role, TaskManager},
+	sc_telemetry::TelemetryHandle,
+	sc_transaction_pool_api::OffchainTransactionPoolFactory,
+	sp_consensus::DisableProofRecording,
+	sp_core::traits::SpawnEssentialNamed,
+	sp_keystore::KeystorePtr,
+	substrate_prometheus_endpoint::Registry,
+};
+use std::{path::PathBuf, sync::Arc};
+
+/// What an engine needs to build its import queue.
+pub struct ImportQueueParams<'a> {
+	pub client: Arc<FullClient>,
+	pub select_chain: FullSelectChain,
+	/// Imports verified blocks into the client, through GRANDPA if the engine uses it.
+	pub block_import: BoxBlockImport<Block>,
+	/// Imports GRANDPA justifications, if the engine uses GRANDPA.
+	pub justification_import: Option<BoxJustificationImport<Block>>,
+	/// Submits transactions from the node, such as equivocation reports.
+	pub offchain_tx_pool_factory: OffchainTransactionPoolFactory<Block>,
+	/// Spawns the import queue's tasks, stopping the node if they fail.
+	pub spawner: Box<dyn SpawnEssentialNamed>,
+	pub registry: Option<&'a Registry>,
+}
+
+/// What an engine needs to author blocks.
+pub struct AuthoringParams<'a> {
+	pub client: Arc<FullClient>,
+	pub select_chain: FullSelectChain,
+	/// Imports authored blocks into the client, through GRANDPA if the engine uses it.
+	pub block_import: BoxBlockImport<Block>,
+	/// Builds blocks from the inherents and the ready transactions of the pool.
+	pub proposer: ProposerFactory<FullPool, FullClient, DisableProofRecording>,
+	pub transaction_pool: Arc<FullPool>,
+	pub keystore: KeystorePtr,
+	pub keystore_path: Option<PathBuf>,
+	pub dev_key_seed: Option<String>,
+	pub sync_service: Arc<SyncingService<Block>>,
+	pub role: Role,
+	/// Where engines register their Prometheus metrics, if enabled.
+	pub registry: Option<Registry>,
+	pub telemetry: Option<TelemetryHandle>,
+	pub task_manager: &'a TaskManager,
+}
+
+/// A consensus engine the node can run, selected with `--consensus`.
+///
+/// To add an engine, implement this trait in a module under `consensus/` and list it in
+/// [`ENGINES`].
+pub trait ConsensusEngine {
+	/// Parses a `--consensus` identifier, returning `None` if it names another engine.
+	///
+	/// Engines may read their own flags from `cli`, and should reject inconsistent ones.
+	fn parse(id: &str, cli: &Cli) -> Option<Result<Self, String>>
+	where
+		Self: Sized;
+
+	/// The name of the engine, as in its `--consensus` identifiers.
+	fn name(&self) -> &'static str;
+
+	/// Whether blocks are finalized by GRANDPA. Engines finalizing their own blocks return `false`.
+	fn grandpa_finality(&self) -> bool {
+		false
+	}
+
+	/// Builds the queue verifying and importing blocks received from the network.
+	fn import_queue(
+		&self,
+		params: ImportQueueParams<'_>,
+	) -> Result<DefaultImportQueue<Block>, ServiceError>;
+
+	/// RPC methods specific to the engine, merged into the node's RPC server of a node of `role`.
+	fn rpc_extensions(&self, _: &Role) -> Result<Option<RpcModule<()>>, ServiceError> {
+		Ok(None)
+	}
+
+	/// Pauses, resumes and stops the authoring task, if the engine supports it.
+	fn authoring_handle(&self) -> Option<AuthoringHandle> {
+		None
+	}
+
+	/// Spawns the tasks authoring blocks, if this node authors any.
+	fn start_authoring(self: Box<Self>, params: AuthoringParams<'_>) -> Result<(), ServiceError>;
+}
+
+type Parser = fn(&str, &Cli) -> Option<Result<Box<dyn ConsensusEngine>, String>>;
+
+fn parser<E: ConsensusEngine + 'static>(
+	id: &str,
+	cli: &Cli,
+) -> Option<Result<Box<dyn ConsensusEngine>, String>> {
+	E::parse(id, cli).map(|engine| engine.map(|engine| Box::new(engine) as Box<_>))
+}
+
+/// The engines selectable with `--consensus`.
+pub const ENGINES: &[Parser] = &[
+	parser::<ManualSealEngine>,
+	parser::<InstantSealEngine>,
+	parser::<RoundRobinEngine>,
+	parser::<PowEngine>,
+];
+
+/// Selects the engine named by the `--consensus` identifier `id`.
+pub fn parse_engine(id: &str, cli: &Cli) -> Result<Box<dyn ConsensusEngine>, String> {
+	ENGINES
+		.iter()
+		.find_map(|parse| parse(id, cli))
+		.unwrap_or_else(|| Err(format!("incorrect consensus identifier: {}", id)))
+}
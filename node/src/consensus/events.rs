@@ -0,0 +1,173 @@
+use super::clock::{Clock, SystemClock};
+use log::{debug, info, warn};
+use polkadot_sdk::sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_INFO};
+use serde::Serialize;
+use std::{
+	fmt,
+	fs::{File, OpenOptions},
+	io::{self, Write},
+	path::Path,
+	sync::Mutex,
+};
+
+/// What a validator decided on its turn check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TurnDecision {
+	/// Author the block, skipping this many authorities that missed their turn.
+	Author { skipped: u32 },
+	/// Wait for the scheduled authority, or for its missed-turn timeout.
+	Wait,
+	/// Not author at all: we hold no key of the authority set.
+	NotAuthority,
+}
+
+/// Something the round-robin authoring task did.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ConsensusEvent {
+	/// Authoring started.
+	Started,
+	/// Checked whether we may author block `number` in `slot`.
+	TurnEvaluated {
+		number: u64,
+		slot: u64,
+		leader: u32,
+		own_index: Option<u32>,
+		authorities: usize,
+		decision: TurnDecision,
+	},
+	/// Built and sealed block `number` as authority `authority_index`.
+	BlockAuthored { number: u64, hash: String, authority_index: u32, skipped: u32 },
+	/// Imported our block `number`.
+	ImportSucceeded { number: u64, hash: String },
+	/// Failed to build or import our block `number`.
+	ImportFailed { number: u64, error: String },
+	/// Authoring was paused through its handle.
+	Paused,
+	/// Authoring was resumed after a pause.
+	Resumed,
+	/// Authoring stopped.
+	Stopped,
+}
+
+impl ConsensusEvent {
+	fn name(&self) -> &'static str {
+		match self {
+			ConsensusEvent::Started => "round_robin.started",
+			ConsensusEvent::TurnEvaluated { .. } => "round_robin.turn_evaluated",
+			ConsensusEvent::BlockAuthored { .. } => "round_robin.block_authored",
+			ConsensusEvent::ImportSucceeded { .. } => "round_robin.import_succeeded",
+			ConsensusEvent::ImportFailed { .. } => "round_robin.import_failed",
+			ConsensusEvent::Paused => "round_robin.paused",
+			ConsensusEvent::Resumed => "round_robin.resumed",
+			ConsensusEvent::Stopped => "round_robin.stopped",
+		}
+	}
+}
+
+impl fmt::Display for ConsensusEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ConsensusEvent::Started => write!(f, "🚀 Starting round-robin consensus"),
+			ConsensusEvent::TurnEvaluated {
+				number,
+				slot,
+				leader,
+				own_index,
+				authorities,
+				decision,
+			} => {
+				let own_index =
+					own_index.map_or_else(|| "-".to_string(), |index| index.to_string());
+				write!(
+					f,
+					"🔄 Block #{} | Slot {} | Leader {} | Our ID {} | Total Validators {} | ",
+					number, slot, leader, own_index, authorities
+				)?;
+				match decision {
+					TurnDecision::Author { skipped: 0 } => write!(f, "our turn"),
+					TurnDecision::Author { skipped } => {
+						write!(f, "authoring after {} missed turns", skipped)
+					},
+					TurnDecision::Wait => write!(f, "waiting"),
+					TurnDecision::NotAuthority => write!(f, "not an authority"),
+				}
+			},
+			ConsensusEvent::BlockAuthored { number, hash, authority_index, skipped: 0 } => {
+				write!(f, "🎯 Validator {} authored block #{} ({})", authority_index, number, hash)
+			},
+			ConsensusEvent::BlockAuthored { number, hash, authority_index, skipped } => write!(
+				f,
+				"⏭️  Validator {} authored block #{} ({}) in place of {} authorities that missed \
+				 their turn",
+				authority_index, number, hash, skipped
+			),
+			ConsensusEvent::ImportSucceeded { number, hash } => {
+				write!(f, "📥 Imported own block #{} ({})", number, hash)
+			},
+			ConsensusEvent::ImportFailed { number, error } => {
+				write!(f, "❌ Failed to author block #{}: {}", number, error)
+			},
+			ConsensusEvent::Paused => write!(f, "⏸️  Round-robin authoring paused"),
+			ConsensusEvent::Resumed => write!(f, "▶️  Round-robin authoring resumed"),
+			ConsensusEvent::Stopped => write!(f, "Round-robin authoring stopped"),
+		}
+	}
+}
+
+/// A line of the JSON events file: an event and when it happened, in milliseconds since the Unix
+/// epoch.
+#[derive(Serialize)]
+struct JsonLine<'a> {
+	timestamp: u64,
+	#[serde(flatten)]
+	event: &'a ConsensusEvent,
+}
+
+/// Delivers consensus events to the `round-robin` log target, to telemetry and, if configured, as
+/// JSON lines to a file. By default only to the log.
+///
+/// Turn checks are logged at debug level, since they happen on every block on every validator.
+#[derive(Default)]
+pub struct ConsensusEvents {
+	telemetry: Option<TelemetryHandle>,
+	json: Option<Mutex<File>>,
+}
+
+impl ConsensusEvents {
+	/// Sends events to `telemetry` and appends them to the file at `json_path`, if given.
+	pub fn new(telemetry: Option<TelemetryHandle>, json_path: Option<&Path>) -> io::Result<Self> {
+		let json = json_path
+			.map(|path| OpenOptions::new().create(true).append(true).open(path))
+			.transpose()?
+			.map(Mutex::new);
+		Ok(Self { telemetry, json })
+	}
+
+	pub fn emit(&self, event: ConsensusEvent) {
+		match &event {
+			ConsensusEvent::TurnEvaluated { .. } | ConsensusEvent::ImportSucceeded { .. } => {
+				debug!(target: "round-robin", "{}", event)
+			},
+			ConsensusEvent::ImportFailed { .. } => warn!(target: "round-robin", "{}", event),
+			_ => info!(target: "round-robin", "{}", event),
+		}
+
+		if let Some(json) = &self.json {
+			let timestamp = SystemClock.now().as_millis() as u64;
+			let line = serde_json::to_string(&JsonLine { timestamp, event: &event })
+				.expect("Events always serialize; qed");
+			let mut file = json.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+			if let Err(e) = writeln!(file, "{}", line) {
+				warn!(target: "round-robin", "Could not write consensus event: {}", e);
+			}
+		}
+
+		let verbosity = match &event {
+			ConsensusEvent::TurnEvaluated { .. } => CONSENSUS_DEBUG,
+			_ => CONSENSUS_INFO,
+		};
+		telemetry!(self.telemetry; verbosity; event.name(); "event" => &event);
+	}
+}
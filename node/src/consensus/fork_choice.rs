@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use codec::{Decode, Encode};
+use futures::lock::Mutex;
+use polkadot_sdk::{
+	sc_client_api::{AuxStore, HeaderBackend},
+	sc_consensus::{
+		BlockCheckParams, BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult,
+	},
+	sp_api::ProvideRuntimeApi,
+	sp_consensus::Error as ConsensusError,
+	sp_runtime::traits::{Block as BlockT, Header as HeaderT},
+};
+use sp_consensus_round_robin::{find_pre_digest, RoundRobinApi};
+use std::{cmp::Ordering, marker::PhantomData, sync::Arc};
+
+/// Prefix of the auxiliary storage keys under which chain weights are kept, followed by the block
+/// hash.
+const CHAIN_WEIGHT_KEY: &[u8] = b"round_robin_chain_weight";
+
+/// How well a chain followed the round-robin schedule. The best block is the head of the heaviest
+/// chain.
+///
+/// Every block adds the number of authorities minus the authorities it skipped, so a block by the
+/// scheduled leader outweighs one authored in its place, while any block still outweighs none.
+/// Between chains of equal authorship, the one that skipped fewer slots is heavier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode)]
+pub struct ChainWeight {
+	/// The sum of the authorship weights of the chain's blocks.
+	pub authorship: u64,
+	/// The slots without a block since the first block of the chain, if turns are given by slots.
+	pub skipped_slots: u64,
+}
+
+impl ChainWeight {
+	/// The weight of the chain extended by a block skipping `skipped` of `authorities_len`
+	/// authorities and `skipped_slots` slots after its parent.
+	pub fn extend(self, authorities_len: usize, skipped: u32, skipped_slots: u64) -> Self {
+		let block = (authorities_len as u64).saturating_sub(skipped as u64).max(1);
+		Self {
+			authorship: self.authorship.saturating_add(block),
+			skipped_slots: self.skipped_slots.saturating_add(skipped_slots),
+		}
+	}
+}
+
+impl Ord for ChainWeight {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.authorship
+			.cmp(&other.authorship)
+			.then_with(|| other.skipped_slots.cmp(&self.skipped_slots))
+	}
+}
+
+impl PartialOrd for ChainWeight {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+fn chain_weight_key<Hash: Encode>(hash: &Hash) -> Vec<u8> {
+	(CHAIN_WEIGHT_KEY, hash).encode()
+}
+
+/// Loads the weight of the chain ending at `hash`. Blocks imported without one, such as genesis,
+/// weigh nothing.
+pub fn load_chain_weight<Block: BlockT, Client: AuxStore>(
+	client: &Client,
+	hash: Block::Hash,
+) -> Result<ChainWeight, String> {
+	let weight = client
+		.get_aux(&chain_weight_key(&hash))
+		.map_err(|e| format!("Could not load chain weight of {:?}: {}", hash, e))?;
+	match weight {
+		Some(weight) => ChainWeight::decode(&mut &weight[..])
+			.map_err(|e| format!("Corrupt chain weight of {:?}: {}", hash, e)),
+		None => Ok(ChainWeight::default()),
+	}
+}
+
+/// Imports blocks through `inner`, recording the weight of each block's chain along with it and
+/// making a block the best one if its chain is heavier than that of the best block at the time of
+/// import.
+///
+/// Wraps the block import of the import queue and that of authoring alike, so received and own
+/// blocks are weighed against the same best block.
+pub struct RoundRobinBlockImport<Block, Inner, Client> {
+	inner: Inner,
+	client: Arc<Client>,
+	/// Held from weighing a block until it is imported.
+	import_lock: Arc<Mutex<()>>,
+	_phantom: PhantomData<Block>,
+}
+
+impl<Block, Inner, Client> RoundRobinBlockImport<Block, Inner, Client> {
+	pub fn new(inner: Inner, client: Arc<Client>) -> Self {
+		Self { inner, client, import_lock: Arc::default(), _phantom: PhantomData }
+	}
+
+	/// Shares `import_lock` with the other block imports into the same chain, so blocks they import
+	/// concurrently are each weighed against the best block as of their own import.
+	pub fn with_import_lock(mut self, import_lock: Arc<Mutex<()>>) -> Self {
+		self.import_lock = import_lock;
+		self
+	}
+}
+
+impl<Block, Inner, Client> RoundRobinBlockImport<Block, Inner, Client>
+where
+	Block: BlockT,
+	Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: RoundRobinApi<Block>,
+{
+	/// The weight of the chain ending at `header`, with slots without a block counting against it
+	/// if turns are given by slots.
+	fn chain_weight(&self, header: &Block::Header) -> Result<ChainWeight, String> {
+		let pre_digest = find_pre_digest(header)
+			.ok_or_else(|| "Header is missing the round-robin pre-digest".to_string())?;
+		let parent_hash = *header.parent_hash();
+		let parent = self
+			.client
+			.header(parent_hash)
+			.map_err(|e| format!("Could not fetch parent header {:?}: {}", parent_hash, e))?
+			.ok_or_else(|| format!("Parent header {:?} not found", parent_hash))?;
+
+		let runtime_api = self.client.runtime_api();
+		let authorities_len = runtime_api
+			.authorities(parent_hash)
+			.map_err(|e| format!("Could not fetch authorities at {:?}: {}", parent_hash, e))?
+			.len();
+		let slots = runtime_api
+			.slot_duration(parent_hash)
+			.map_err(|e| format!("Could not fetch slot duration at {:?}: {}", parent_hash, e))?
+			.is_some();
+		let skipped_slots = match find_pre_digest(&parent) {
+			Some(parent_digest) if slots =>
+				(*pre_digest.slot).saturating_sub(*parent_digest.slot).saturating_sub(1),
+			_ => 0,
+		};
+
+		Ok(load_chain_weight::<Block, _>(&*self.client, parent_hash)?.extend(
+			authorities_len,
+			pre_digest.skipped,
+			skipped_slots,
+		))
+	}
+}
+
+#[async_trait]
+impl<Block, Inner, Client> BlockImport<Block> for RoundRobinBlockImport<Block, Inner, Client>
+where
+	Block: BlockT,
+	Inner: BlockImport<Block, Error = ConsensusError> + Send + Sync,
+	Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync,
+	Client::Api: RoundRobinApi<Block>,
+{
+	type Error = ConsensusError;
+
+	async fn check_block(
+		&self,
+		block: BlockCheckParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block).await
+	}
+
+	async fn import_block(
+		&self,
+		mut block: BlockImportParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		let _import = self.import_lock.lock().await;
+
+		// Blocks imported without executing them (e.g. during warp sync) cannot be weighed on a
+		// parent whose state we don't have. The block whose state is imported becomes the best.
+		if block.with_state() || block.state_action.skip_execution_checks() {
+			block.fork_choice = Some(ForkChoiceStrategy::Custom(block.with_state()));
+			return self.inner.import_block(block).await;
+		}
+
+		let weight = self.chain_weight(&block.header).map_err(ConsensusError::ClientImport)?;
+		let best_hash = self.client.info().best_hash;
+		let best_weight = load_chain_weight::<Block, _>(&*self.client, best_hash)
+			.map_err(ConsensusError::ClientImport)?;
+
+		let hash = block.post_hash();
+		block.auxiliary.push((chain_weight_key(&hash), Some(weight.encode())));
+		block.fork_choice = Some(ForkChoiceStrategy::Custom(weight > best_weight));
+		self.inner.import_block(block).await
+	}
+}
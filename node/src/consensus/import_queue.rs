@@ -0,0 +1,464 @@
+use super::clock::{Clock, SystemClock};
+use async_trait::async_trait;
+use codec::Encode;
+use log::{debug, info, warn};
+use polkadot_sdk::{
+	sc_client_api::{AuxStore, HeaderBackend},
+	sc_consensus::{
+		BasicQueue, BlockImport, BlockImportParams, BoxJustificationImport, DefaultImportQueue,
+		Verifier,
+	},
+	sc_consensus_slots,
+	sc_transaction_pool_api::OffchainTransactionPoolFactory,
+	sp_api::{ApiExt, ProvideRuntimeApi},
+	sp_block_builder::BlockBuilder,
+	sp_consensus::{BlockOrigin, Error as ConsensusError},
+	sp_core::traits::SpawnEssentialNamed,
+	sp_inherents::{
+		CheckInherentsResult, CreateInherentDataProviders, InherentData, InherentDataProvider,
+	},
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT, NumberFor},
+		DigestItem, SaturatedConversion,
+	},
+	sp_timestamp::{InherentError as TimestampError, Timestamp},
+	substrate_prometheus_endpoint::Registry,
+};
+use sp_consensus_round_robin::{
+	fallback_author, fallback_order, find_pre_digest, AuthorityId, AuthorityIndex,
+	CompatibleDigestItem, EquivocationProof, RoundRobinApi, Slot, SlotDuration,
+};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+/// How many slots ahead of our clock an imported block may be, to tolerate clock drift.
+const MAX_SLOT_DRIFT: u64 = 1;
+
+/// How far ahead of our clock the timestamp of an imported block may be by default.
+pub const DEFAULT_MAX_TIMESTAMP_DRIFT: Duration = Duration::from_secs(10);
+
+/// The largest allowed drift: `pallet_timestamp` rejects timestamps further ahead of our clock
+/// whatever we allow.
+pub const MAX_TIMESTAMP_DRIFT: Duration = Duration::from_secs(30);
+
+/// Prefix of the auxiliary storage keys under which equivocation proofs are kept, followed by the
+/// offender and the slot.
+const EQUIVOCATION_PROOF_KEY: &[u8] = b"round_robin_equivocation_proof";
+
+/// Verifies that imported blocks were sealed by the authority scheduled for their slot and carry
+/// valid inherents, and reports authorities sealing two blocks for the same slot.
+pub struct RoundRobinVerifier<Block: BlockT, Client, CIDP> {
+	client: Arc<Client>,
+	offchain_tx_pool_factory: OffchainTransactionPoolFactory<Block>,
+	/// Creates the inherent data the inherents of imported blocks are checked against.
+	create_inherent_data_providers: CIDP,
+	max_timestamp_drift: Duration,
+	clock: Arc<dyn Clock>,
+	_phantom: PhantomData<Block>,
+}
+
+impl<Block: BlockT, Client, CIDP> RoundRobinVerifier<Block, Client, CIDP>
+where
+	Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: RoundRobinApi<Block> + BlockBuilder<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()>,
+{
+	pub fn new(
+		client: Arc<Client>,
+		offchain_tx_pool_factory: OffchainTransactionPoolFactory<Block>,
+		create_inherent_data_providers: CIDP,
+	) -> Self {
+		Self {
+			client,
+			offchain_tx_pool_factory,
+			create_inherent_data_providers,
+			max_timestamp_drift: DEFAULT_MAX_TIMESTAMP_DRIFT,
+			clock: Arc::new(SystemClock),
+			_phantom: PhantomData,
+		}
+	}
+
+	/// Rejects blocks whose timestamp is more than `max_timestamp_drift` ahead of our clock, at
+	/// most [`MAX_TIMESTAMP_DRIFT`].
+	pub fn with_max_timestamp_drift(mut self, max_timestamp_drift: Duration) -> Self {
+		self.max_timestamp_drift = max_timestamp_drift.min(MAX_TIMESTAMP_DRIFT);
+		self
+	}
+
+	/// Reads the time from `clock` rather than the system clock.
+	pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+		self.clock = clock;
+		self
+	}
+
+	/// Creates the inherent data providers of our own block on `parent_hash`, and their data.
+	async fn create_inherent_data(
+		&self,
+		parent_hash: Block::Hash,
+	) -> Result<(CIDP::InherentDataProviders, InherentData), String> {
+		let providers = self
+			.create_inherent_data_providers
+			.create_inherent_data_providers(parent_hash, ())
+			.await
+			.map_err(|e| format!("Could not create inherent data providers: {}", e))?;
+		let inherent_data = providers
+			.create_inherent_data()
+			.await
+			.map_err(|e| format!("Could not create inherent data: {}", e))?;
+		Ok((providers, inherent_data))
+	}
+
+	/// Checks the inherents of the block with `pre_header` and `body` against our own
+	/// `inherent_data`, and that its timestamp is at most the allowed drift ahead of our clock.
+	///
+	/// Returns the errors the runtime finds, for [`Self::handle_inherent_errors`].
+	fn check_inherents(
+		&self,
+		pre_header: Block::Header,
+		body: &Vec<Block::Extrinsic>,
+		inherent_data: InherentData,
+	) -> Result<CheckInherentsResult, String> {
+		let number = *pre_header.number();
+		let parent_hash = *pre_header.parent_hash();
+		let runtime_api = self.client.runtime_api();
+
+		let timestamp = runtime_api
+			.block_timestamp(parent_hash, body)
+			.map_err(|e| format!("Could not read the timestamp of block #{}: {}", number, e))?
+			.ok_or_else(|| format!("Block #{} sets no timestamp", number))?;
+		let now = self.clock.timestamp().as_millis();
+		if timestamp > now.saturating_add(self.max_timestamp_drift.as_millis() as u64) {
+			return Err(format!(
+				"Block #{} has timestamp {} which is more than {:?} ahead of ours ({})",
+				number, timestamp, self.max_timestamp_drift, now
+			));
+		}
+
+		runtime_api
+			.check_inherents(parent_hash, Block::new(pre_header, body.clone()), inherent_data)
+			.map_err(|e| format!("Could not check the inherents of block #{}: {}", number, e))
+	}
+
+	/// Lets `providers` handle each inherent error in `result`, as they would for our own blocks,
+	/// rejecting the block with `number` on the first error they cannot handle.
+	///
+	/// The timestamp provider handles none: a timestamp too far ahead of ours, or less than the
+	/// runtime's minimum period after its parent's, rejects the block.
+	async fn handle_inherent_errors(
+		&self,
+		number: NumberFor<Block>,
+		result: CheckInherentsResult,
+		providers: &CIDP::InherentDataProviders,
+	) -> Result<(), String> {
+		for (identifier, error) in result.into_errors() {
+			let reason = match TimestampError::try_from(&identifier, &error) {
+				Some(TimestampError::TooEarly) =>
+					"its timestamp is less than the minimum period after its parent's".to_string(),
+				Some(TimestampError::TooFarInFuture) =>
+					"its timestamp is too far in the future".to_string(),
+				None => format!(
+					"its inherent {} is invalid: {:?}",
+					String::from_utf8_lossy(&identifier),
+					error
+				),
+			};
+			match providers.try_handle_error(&identifier, &error).await {
+				Some(Ok(())) => {},
+				Some(Err(e)) =>
+					return Err(format!("Block #{} is rejected: {} ({})", number, reason, e)),
+				None => return Err(format!("Block #{} is rejected: {}", number, reason)),
+			}
+		}
+		Ok(())
+	}
+
+	/// Remembers the sealed `header` of `author` for `slot` and, if the author already sealed
+	/// another header for that slot, stores both as an equivocation proof and reports it to the
+	/// runtime. When turns are given by height, only headers on the same parent count.
+	///
+	/// `slot_now` is the latest slot we know of; headers are remembered for a limited number of
+	/// slots before it.
+	fn check_equivocation(
+		&self,
+		origin: BlockOrigin,
+		slot_now: Slot,
+		slot: Slot,
+		header: &Block::Header,
+		author: &AuthorityId,
+		turns_by_height: bool,
+	) -> Result<(), String> {
+		let proof = if turns_by_height {
+			// An authority may author at the same height again on another fork, so only headers
+			// on the same parent are compared
+			let signer = (author.clone(), *header.parent_hash());
+			sc_consensus_slots::check_equivocation(&*self.client, slot_now, slot, header, &signer)
+				.map(|proof| {
+					proof.map(|proof| EquivocationProof {
+						offender: proof.offender.0,
+						slot: proof.slot,
+						first_header: proof.first_header,
+						second_header: proof.second_header,
+					})
+				})
+		} else {
+			sc_consensus_slots::check_equivocation(&*self.client, slot_now, slot, header, author)
+		};
+		let Some(proof) = proof.map_err(|e| format!("Could not check for equivocation: {}", e))?
+		else {
+			return Ok(());
+		};
+
+		warn!(
+			target: "round-robin",
+			"Authority {:?} equivocated in slot {}: sealed both {:?} and {:?}",
+			author,
+			*slot,
+			proof.first_header.hash(),
+			proof.second_header.hash(),
+		);
+
+		let key = (EQUIVOCATION_PROOF_KEY, author, slot).encode();
+		self.client
+			.insert_aux(&[(&key[..], &proof.encode()[..])], &[])
+			.map_err(|e| format!("Could not store equivocation proof: {}", e))?;
+
+		// Equivocations met while catching up are most likely reported already
+		if origin == BlockOrigin::NetworkInitialSync {
+			return Ok(());
+		}
+
+		let best_hash = self.client.info().best_hash;
+		let mut runtime_api = self.client.runtime_api();
+		runtime_api
+			.register_extension(self.offchain_tx_pool_factory.offchain_transaction_pool(best_hash));
+		match runtime_api.submit_report_equivocation_unsigned_extrinsic(best_hash, proof) {
+			Ok(Some(())) => {
+				info!(target: "round-robin", "Reported equivocation of {:?} in slot {}", author, *slot)
+			},
+			Ok(None) => {
+				warn!(target: "round-robin", "Could not submit equivocation report of {:?}", author)
+			},
+			Err(e) => warn!(
+				target: "round-robin",
+				"Could not submit equivocation report of {:?}: {}",
+				author,
+				e
+			),
+		}
+
+		Ok(())
+	}
+}
+
+/// Strips the seal from `header` and checks it against the authority scheduled for the block, as
+/// of `now`.
+///
+/// A block skipping authorities that missed their turn is only accepted from `fallback_timestamp`
+/// on, the earliest time the parent allows it to be built.
+///
+/// Returns the pre-seal header and the seal digest item.
+fn check_header<Block: BlockT>(
+	mut header: Block::Header,
+	now: Timestamp,
+	parent_slot: Slot,
+	slot_duration: Option<SlotDuration>,
+	fallback_timestamp: Option<u64>,
+	authorities: &[AuthorityId],
+	schedule: &[AuthorityIndex],
+) -> Result<(Block::Header, DigestItem), String> {
+	let seal = header.digest_mut().pop().ok_or_else(|| "Header is unsealed".to_string())?;
+	let signature =
+		seal.as_round_robin_seal().ok_or_else(|| "Header has a bad seal".to_string())?;
+
+	let pre_digest = find_pre_digest(&header)
+		.ok_or_else(|| "Header is missing the round-robin pre-digest".to_string())?;
+
+	let number = (*header.number()).saturated_into::<u64>();
+	let slot = pre_digest.slot;
+	match slot_duration {
+		None if *slot != number => {
+			return Err(format!(
+				"Block #{} claims slot {} but turns are given by height",
+				number, *slot
+			));
+		},
+		None => {},
+		Some(slot_duration) => {
+			if slot <= parent_slot {
+				return Err(format!(
+					"Block #{} claims slot {} which is not after its parent's slot {}",
+					number, *slot, *parent_slot
+				));
+			}
+			let current_slot = Slot::from_timestamp(now, slot_duration);
+			if *slot > *current_slot + MAX_SLOT_DRIFT {
+				return Err(format!(
+					"Block #{} claims slot {} which is ahead of the current slot {}",
+					number, *slot, *current_slot
+				));
+			}
+		},
+	}
+
+	let skipped = pre_digest.skipped;
+	if skipped > 0 {
+		let fallbacks = fallback_order(schedule).len();
+		if skipped as usize >= fallbacks {
+			return Err(format!(
+				"Block #{} skips {} authorities out of {} with turns",
+				number, skipped, fallbacks
+			));
+		}
+		match fallback_timestamp {
+			None => {
+				return Err(format!(
+					"Block #{} skips {} authorities but missed turns are never skipped",
+					number, skipped
+				));
+			},
+			Some(fallback_timestamp) if now.as_millis() < fallback_timestamp => {
+				return Err(format!(
+					"Block #{} skips {} authorities before their missed-turn timeout elapsed",
+					number, skipped
+				));
+			},
+			Some(_) => {},
+		}
+	}
+
+	let scheduled =
+		fallback_author(slot, skipped, schedule).ok_or_else(|| "Schedule is empty".to_string())?;
+	if scheduled as usize >= authorities.len() {
+		return Err(format!("Schedule names validator {} outside the authority set", scheduled));
+	}
+
+	if pre_digest.authority_index != scheduled {
+		return Err(format!(
+			"Block #{} claims author {} but validator {} is scheduled for slot {} after {} skips",
+			number, pre_digest.authority_index, scheduled, *slot, skipped
+		));
+	}
+
+	let author = &authorities[scheduled as usize];
+	let pre_hash = header.hash();
+	if !author.verify(pre_hash.as_ref(), &signature) {
+		return Err(format!("Bad signature on block #{} ({})", number, pre_hash));
+	}
+
+	Ok((header, seal))
+}
+
+#[async_trait]
+impl<Block, Client, CIDP> Verifier<Block> for RoundRobinVerifier<Block, Client, CIDP>
+where
+	Block: BlockT,
+	Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync,
+	Client::Api: RoundRobinApi<Block> + BlockBuilder<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync,
+{
+	async fn verify(
+		&self,
+		mut block: BlockImportParams<Block>,
+	) -> Result<BlockImportParams<Block>, String> {
+		// Blocks imported together with their state (e.g. during warp sync) cannot be checked
+		// against the authority set of a parent we don't have.
+		if block.with_state() || block.state_action.skip_execution_checks() {
+			return Ok(block);
+		}
+
+		let hash = block.header.hash();
+		let parent_hash = *block.header.parent_hash();
+		let parent_header = self
+			.client
+			.header(parent_hash)
+			.map_err(|e| format!("Could not fetch parent header {:?}: {}", parent_hash, e))?
+			.ok_or_else(|| format!("Parent header {:?} not found", parent_hash))?;
+		let parent_slot = find_pre_digest(&parent_header).map(|d| d.slot).unwrap_or_default();
+		// Created before any runtime API call, which cannot be held across an await
+		let (inherent_data_providers, inherent_data) = match block.body {
+			Some(_) => {
+				let (providers, inherent_data) = self.create_inherent_data(parent_hash).await?;
+				(Some(providers), Some(inherent_data))
+			},
+			None => (None, None),
+		};
+
+		let runtime_api = self.client.runtime_api();
+		let authorities = runtime_api
+			.authorities(parent_hash)
+			.map_err(|e| format!("Could not fetch authorities at {:?}: {}", parent_hash, e))?;
+		let pre_digest = find_pre_digest(&block.header)
+			.ok_or_else(|| "Header is missing the round-robin pre-digest".to_string())?;
+		let schedule = runtime_api
+			.schedule(parent_hash, pre_digest.slot)
+			.map_err(|e| format!("Could not fetch schedule at {:?}: {}", parent_hash, e))?;
+		let slot_duration = runtime_api
+			.slot_duration(parent_hash)
+			.map_err(|e| format!("Could not fetch slot duration at {:?}: {}", parent_hash, e))?;
+		let fallback_timestamp = if pre_digest.skipped > 0 {
+			runtime_api.fallback_timestamp(parent_hash, pre_digest.skipped).map_err(|e| {
+				format!("Could not fetch missed-turn timeout at {:?}: {}", parent_hash, e)
+			})?
+		} else {
+			None
+		};
+
+		let (pre_header, seal) = check_header::<Block>(
+			block.header.clone(),
+			self.clock.timestamp(),
+			parent_slot,
+			slot_duration,
+			fallback_timestamp,
+			&authorities,
+			&schedule,
+		)?;
+		debug!(target: "round-robin", "Verified seal of block {:?}", hash);
+
+		let inherent_errors = match (&block.body, inherent_data) {
+			(Some(body), Some(inherent_data)) =>
+				Some(self.check_inherents(pre_header.clone(), body, inherent_data)?),
+			_ => None,
+		};
+		drop(runtime_api);
+		if let (Some(result), Some(providers)) = (inherent_errors, &inherent_data_providers) {
+			self.handle_inherent_errors(*pre_header.number(), result, providers).await?;
+		}
+
+		let slot_now = match slot_duration {
+			Some(slot_duration) => Slot::from_timestamp(self.clock.timestamp(), slot_duration),
+			None => Slot::from(self.client.info().best_number.saturated_into::<u64>() + 1),
+		};
+		self.check_equivocation(
+			block.origin,
+			slot_now.max(pre_digest.slot),
+			pre_digest.slot,
+			&block.header,
+			&authorities[pre_digest.authority_index as usize],
+			slot_duration.is_none(),
+		)?;
+
+		block.header = pre_header;
+		block.post_digests.push(seal);
+		block.post_hash = Some(hash);
+
+		Ok(block)
+	}
+}
+
+/// Builds an import queue that only accepts blocks `verifier` accepts, importing finality
+/// justifications through `justification_import` if given.
+pub fn import_queue<Block, Client, CIDP, I>(
+	verifier: RoundRobinVerifier<Block, Client, CIDP>,
+	block_import: I,
+	justification_import: Option<BoxJustificationImport<Block>>,
+	spawner: &impl SpawnEssentialNamed,
+	registry: Option<&Registry>,
+) -> DefaultImportQueue<Block>
+where
+	Block: BlockT,
+	Client: AuxStore + HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	Client::Api: RoundRobinApi<Block> + BlockBuilder<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync + 'static,
+	I: BlockImport<Block, Error = ConsensusError> + Send + Sync + 'static,
+{
+	BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry)
+}
@@ -0,0 +1,152 @@
+use log::warn;
+use polkadot_sdk::{
+	sp_consensus::Error as ConsensusError,
+	sp_core::{crypto::ByteArray, hashing::blake2_256, hexdisplay::HexDisplay},
+	sp_keystore::KeystorePtr,
+};
+use sp_consensus_round_robin::{ml_dsa, AuthorityId, AuthoritySignature, KEY_TYPE};
+use std::{
+	collections::HashMap,
+	fs,
+	io::{self, Write},
+	path::{Path, PathBuf},
+	sync::{Arc, RwLock},
+};
+
+/// Holds post-quantum round-robin authority keys, which the node keystore cannot store.
+///
+/// Keys live in the `pq` subdirectory of the keystore, one file per key holding the secret URI
+/// as a JSON string. Without a keystore path (e.g. `--dev`) keys are only kept in memory.
+pub struct PqKeystore {
+	path: Option<PathBuf>,
+	keys: RwLock<HashMap<ml_dsa::AuthorityId, ml_dsa::AuthorityPair>>,
+}
+
+impl PqKeystore {
+	/// Opens the post-quantum keystore next to the keystore at `keystore_path`.
+	pub fn open(keystore_path: Option<&Path>) -> io::Result<Self> {
+		let path = keystore_path.map(|path| path.join("pq"));
+		let mut keys = HashMap::new();
+
+		if let Some(path) = &path {
+			fs::create_dir_all(path)?;
+			for entry in fs::read_dir(path)? {
+				let file = entry?.path();
+				let suri: String = serde_json::from_slice(&fs::read(&file)?)?;
+				match ml_dsa::AuthorityPair::from_string(&suri) {
+					Ok(pair) => {
+						keys.insert(pair.public(), pair);
+					},
+					Err(e) => warn!(target: "round-robin", "Skipping key file {:?}: {}", file, e),
+				}
+			}
+		}
+
+		Ok(Self { path, keys: RwLock::new(keys) })
+	}
+
+	/// Adds the key derived from `suri`, persisting it if the keystore has a path.
+	pub fn insert(&self, suri: &str) -> io::Result<ml_dsa::AuthorityId> {
+		let pair = ml_dsa::AuthorityPair::from_string(suri)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		let public = pair.public();
+
+		if let Some(path) = &self.path {
+			let file = path.join(format!(
+				"{}{}",
+				HexDisplay::from(&ml_dsa::KEY_TYPE.0),
+				HexDisplay::from(&blake2_256(public.as_ref()))
+			));
+			let mut options = fs::OpenOptions::new();
+			options.write(true).create_new(true);
+			// Created private, so the secret is never readable by others
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::OpenOptionsExt;
+				options.mode(0o600);
+			}
+			match options.open(&file) {
+				Ok(mut file) => {
+					file.write_all(&serde_json::to_vec(suri)?)?;
+					file.sync_all()?;
+				},
+				// Named after the public key, so it already holds this key
+				Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+		self.keys
+			.write()
+			.expect("lock is never poisoned; qed")
+			.insert(public.clone(), pair);
+		Ok(public)
+	}
+
+	/// Whether the key for `public` is present.
+	pub fn contains(&self, public: &ml_dsa::AuthorityId) -> bool {
+		self.keys.read().expect("lock is never poisoned; qed").contains_key(public)
+	}
+
+	/// Signs `message` with the key for `public`, if present.
+	pub fn sign(
+		&self,
+		public: &ml_dsa::AuthorityId,
+		message: &[u8],
+	) -> Option<ml_dsa::AuthoritySignature> {
+		self.keys
+			.read()
+			.expect("lock is never poisoned; qed")
+			.get(public)
+			.map(|pair| pair.sign(message))
+	}
+}
+
+/// Signs on behalf of round-robin authorities of either seal scheme: sr25519 keys come from the
+/// node keystore and ML-DSA-65 keys from the [`PqKeystore`].
+#[derive(Clone)]
+pub struct AuthorityKeystore {
+	keystore: KeystorePtr,
+	pq_keystore: Arc<PqKeystore>,
+}
+
+impl AuthorityKeystore {
+	pub fn new(keystore: KeystorePtr, pq_keystore: Arc<PqKeystore>) -> Self {
+		Self { keystore, pq_keystore }
+	}
+
+	/// Whether we hold the key of `authority`.
+	pub fn has_key(&self, authority: &AuthorityId) -> bool {
+		match authority {
+			AuthorityId::Sr25519(public) =>
+				self.keystore.has_keys(&[(public.to_raw_vec(), KEY_TYPE)]),
+			AuthorityId::MlDsa65(public) => self.pq_keystore.contains(public),
+		}
+	}
+
+	/// Signs `message` as `authority`.
+	pub fn sign(
+		&self,
+		authority: &AuthorityId,
+		message: &[u8],
+	) -> Result<AuthoritySignature, ConsensusError> {
+		let signature = match authority {
+			AuthorityId::Sr25519(public) => self
+				.keystore
+				.sr25519_sign(KEY_TYPE, public.as_ref(), message)
+				.map_err(|e| ConsensusError::CannotSign(format!("{}", e)))?
+				.map(|signature| AuthoritySignature::Sr25519(signature.into())),
+			AuthorityId::MlDsa65(public) => self
+				.pq_keystore
+				.sign(public, message)
+				.map(|signature| AuthoritySignature::MlDsa65(Box::new(signature))),
+		};
+
+		signature.ok_or_else(|| {
+			ConsensusError::CannotSign(format!(
+				"Could not find key in keystore. Key: {:?}",
+				authority
+			))
+		})
+	}
+}
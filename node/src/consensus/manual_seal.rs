@@ -0,0 +1,130 @@
+use super::engine::{AuthoringParams, ConsensusEngine, ImportQueueParams};
+use crate::cli::Cli;
+use futures::channel::mpsc;
+use minimal_template_runtime::interface::{Hash, OpaqueBlock as Block};
+use polkadot_sdk::{
+	sc_consensus::DefaultImportQueue,
+	sc_consensus_manual_seal::{self, EngineCommand},
+	sc_service::error::Error as ServiceError,
+	sp_timestamp,
+};
+use std::time::Duration;
+
+/// Seals a block every `block_time` milliseconds.
+pub struct ManualSealEngine {
+	block_time: u64,
+	commands: (mpsc::Sender<EngineCommand<Hash>>, mpsc::Receiver<EngineCommand<Hash>>),
+}
+
+impl ConsensusEngine for ManualSealEngine {
+	fn parse(id: &str, _: &Cli) -> Option<Result<Self, String>> {
+		let block_time = id.strip_prefix("manual-seal-")?;
+		Some(
+			block_time
+				.parse()
+				.map(|block_time| Self { block_time, commands: mpsc::channel(1024) })
+				.map_err(|_| "invalid block time".into()),
+		)
+	}
+
+	fn name(&self) -> &'static str {
+		"manual-seal"
+	}
+
+	fn import_queue(
+		&self,
+		params: ImportQueueParams<'_>,
+	) -> Result<DefaultImportQueue<Block>, ServiceError> {
+		Ok(sc_consensus_manual_seal::import_queue(
+			params.block_import,
+			&params.spawner,
+			params.registry,
+		))
+	}
+
+	fn start_authoring(self: Box<Self>, params: AuthoringParams<'_>) -> Result<(), ServiceError> {
+		let Self { block_time, commands: (mut sink, commands_stream) } = *self;
+
+		params.task_manager.spawn_handle().spawn("block_authoring", None, async move {
+			loop {
+				futures_timer::Delay::new(Duration::from_millis(block_time)).await;
+				sink.try_send(EngineCommand::SealNewBlock {
+					create_empty: true,
+					finalize: true,
+					parent_hash: None,
+					sender: None,
+				})
+				.unwrap();
+			}
+		});
+
+		let manual_seal = sc_consensus_manual_seal::ManualSealParams {
+			block_import: params.block_import,
+			env: params.proposer,
+			client: params.client,
+			pool: params.transaction_pool,
+			select_chain: params.select_chain,
+			commands_stream: Box::pin(commands_stream),
+			consensus_data_provider: None,
+			create_inherent_data_providers: move |_, ()| async move {
+				Ok(sp_timestamp::InherentDataProvider::from_system_time())
+			},
+		};
+		let authorship_future = sc_consensus_manual_seal::run_manual_seal(manual_seal);
+
+		params.task_manager.spawn_essential_handle().spawn_blocking(
+			"manual-seal",
+			None,
+			authorship_future,
+		);
+
+		Ok(())
+	}
+}
+
+/// Seals a block as soon as a transaction enters the pool.
+pub struct InstantSealEngine;
+
+impl ConsensusEngine for InstantSealEngine {
+	fn parse(id: &str, _: &Cli) -> Option<Result<Self, String>> {
+		(id == "instant-seal").then_some(Ok(Self))
+	}
+
+	fn name(&self) -> &'static str {
+		"instant-seal"
+	}
+
+	fn import_queue(
+		&self,
+		params: ImportQueueParams<'_>,
+	) -> Result<DefaultImportQueue<Block>, ServiceError> {
+		Ok(sc_consensus_manual_seal::import_queue(
+			params.block_import,
+			&params.spawner,
+			params.registry,
+		))
+	}
+
+	fn start_authoring(self: Box<Self>, params: AuthoringParams<'_>) -> Result<(), ServiceError> {
+		let instant_seal = sc_consensus_manual_seal::InstantSealParams {
+			block_import: params.block_import,
+			env: params.proposer,
+			client: params.client,
+			pool: params.transaction_pool,
+			select_chain: params.select_chain,
+			consensus_data_provider: None,
+			create_inherent_data_providers: move |_, ()| async move {
+				Ok(sp_timestamp::InherentDataProvider::from_system_time())
+			},
+		};
+		let authorship_future = sc_consensus_manual_seal::run_instant_seal(instant_seal);
+
+		params.task_manager.spawn_essential_handle().spawn_blocking(
+			"instant-seal",
+			None,
+			authorship_future,
+		);
+
+		Ok(())
+	}
+}
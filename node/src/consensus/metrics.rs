@@ -0,0 +1,73 @@
+use polkadot_sdk::substrate_prometheus_endpoint::{
+	exponential_buckets, register, Counter, Gauge, Histogram, HistogramOpts, PrometheusError,
+	Registry, U64,
+};
+
+/// Prometheus metrics of round-robin authoring.
+#[derive(Clone)]
+pub struct Metrics {
+	/// Blocks this node authored and imported.
+	pub blocks_authored: Counter<U64>,
+	/// Authorities skipped on the best chain because they missed their turn.
+	pub turns_missed: Counter<U64>,
+	/// How long building, sealing and importing an own block took.
+	pub block_production_duration: Histogram,
+	/// Own blocks the block import failed on or rejected.
+	pub import_failures: Counter<U64>,
+	/// The index of the authority scheduled to author the next block.
+	pub current_leader: Gauge<U64>,
+	/// When this node last authored a block, in seconds since the Unix epoch.
+	pub last_authored_timestamp: Gauge<U64>,
+}
+
+impl Metrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			blocks_authored: register(
+				Counter::new(
+					"substrate_round_robin_blocks_authored_total",
+					"Number of blocks authored and imported by this node",
+				)?,
+				registry,
+			)?,
+			turns_missed: register(
+				Counter::new(
+					"substrate_round_robin_turns_missed_total",
+					"Number of authorities skipped on the best chain for missing their turn",
+				)?,
+				registry,
+			)?,
+			block_production_duration: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"substrate_round_robin_block_production_duration_seconds",
+						"Time taken to build, seal and import an own block",
+					)
+					.buckets(exponential_buckets(0.01, 2.0, 10)?),
+				)?,
+				registry,
+			)?,
+			import_failures: register(
+				Counter::new(
+					"substrate_round_robin_import_failures_total",
+					"Number of own blocks that failed to import",
+				)?,
+				registry,
+			)?,
+			current_leader: register(
+				Gauge::new(
+					"substrate_round_robin_current_leader",
+					"Index of the authority scheduled to author the next block",
+				)?,
+				registry,
+			)?,
+			last_authored_timestamp: register(
+				Gauge::new(
+					"substrate_round_robin_last_authored_timestamp_seconds",
+					"Unix time in seconds at which this node last authored a block",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
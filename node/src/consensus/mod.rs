@@ -1,8 +1,28 @@
 mod basic_block_producer;
 mod block_production;
+mod clock;
+mod control;
+mod engine;
+mod events;
+mod fork_choice;
+mod import_queue;
+mod keystore;
+mod manual_seal;
+mod metrics;
+mod pow;
 
 pub use basic_block_producer::BasicBlockProducer;
 pub use block_production::BlockProducer;
-pub use round_robin::RoundRobinConsensus;
+pub use clock::{Clock, SystemClock};
+pub use control::{AuthoringHandle, AuthoringState, StopOnDrop};
+pub use engine::{parse_engine, AuthoringParams, ConsensusEngine, ImportQueueParams, ENGINES};
+pub use events::{ConsensusEvent, ConsensusEvents, TurnDecision};
+pub use fork_choice::{load_chain_weight, ChainWeight, RoundRobinBlockImport};
+pub use import_queue::{import_queue, RoundRobinVerifier};
+pub use keystore::{AuthorityKeystore, PqKeystore};
+pub use manual_seal::{InstantSealEngine, ManualSealEngine};
+pub use metrics::Metrics;
+pub use pow::{mine, PowEngine, Sha3Algorithm, Sha3Seal, StopMining};
+pub use round_robin::{ChainTiming, RoundRobinConsensus, RoundRobinEngine, RoundRobinTiming};
 
-mod round_robin; 
+mod round_robin;
@@ -0,0 +1,310 @@
+use super::engine::{AuthoringParams, ConsensusEngine, ImportQueueParams};
+use crate::{
+	cli::Cli,
+	service::{FullClient, FullSelectChain},
+};
+use codec::{Decode, Encode};
+use log::{debug, info, warn};
+use minimal_template_runtime::interface::{AccountId, OpaqueBlock};
+use polkadot_sdk::{
+	sc_consensus::{BoxBlockImport, DefaultImportQueue, JustificationSyncLink},
+	sc_consensus_pow::{self, Error as PowError, MiningHandle, PowAlgorithm, PowBlockImport},
+	sc_service::error::Error as ServiceError,
+	sp_api::ProvideRuntimeApi,
+	sp_consensus_pow::{DifficultyApi, Seal as RawSeal},
+	sp_core::{H256, U256},
+	sp_inherents::CreateInherentDataProviders,
+	sp_runtime::{generic::BlockId, traits::Block as BlockT},
+	sp_timestamp,
+};
+use sha3::{Digest, Sha3_256};
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+/// How many nonces a mining thread tries before checking for a new block template.
+const NONCES_PER_ROUND: u64 = 10_000;
+
+/// The seal of a proof-of-work block.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
+pub struct Sha3Seal {
+	/// The difficulty the block was mined at.
+	pub difficulty: U256,
+	/// SHA3-256 of the pre-seal header hash and the nonce.
+	pub work: H256,
+	/// The nonce found by the miner.
+	pub nonce: U256,
+}
+
+impl Sha3Seal {
+	/// Computes the seal for `nonce` over `pre_hash`.
+	pub fn compute(pre_hash: &H256, difficulty: U256, nonce: U256) -> Self {
+		let mut hasher = Sha3_256::new();
+		hasher.update(pre_hash.as_bytes());
+		hasher.update(nonce.encode());
+		let work = H256::from_slice(&hasher.finalize());
+
+		Self { difficulty, work, nonce }
+	}
+
+	/// Whether the work satisfies `difficulty`, i.e. `work * difficulty` does not overflow.
+	pub fn meets(&self, difficulty: U256) -> bool {
+		let work = U256::from_big_endian(self.work.as_bytes());
+		let (_, overflowed) = work.overflowing_mul(difficulty);
+		!overflowed
+	}
+}
+
+/// Proof-of-work over SHA3-256, whose security against quantum search degrades only
+/// quadratically (Grover), unlike the signature schemes it replaces.
+///
+/// The difficulty is read from the runtime at the parent of each block.
+pub struct Sha3Algorithm<Client> {
+	client: Arc<Client>,
+}
+
+impl<Client> Sha3Algorithm<Client> {
+	pub fn new(client: Arc<Client>) -> Self {
+		Self { client }
+	}
+}
+
+impl<Client> Clone for Sha3Algorithm<Client> {
+	fn clone(&self) -> Self {
+		Self::new(self.client.clone())
+	}
+}
+
+impl<Block, Client> PowAlgorithm<Block> for Sha3Algorithm<Client>
+where
+	Block: BlockT<Hash = H256>,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: DifficultyApi<Block, U256>,
+{
+	type Difficulty = U256;
+
+	fn difficulty(&self, parent: Block::Hash) -> Result<U256, PowError<Block>> {
+		self.client.runtime_api().difficulty(parent).map_err(|e| {
+			PowError::Environment(format!("Could not fetch difficulty at {:?}: {}", parent, e))
+		})
+	}
+
+	fn verify(
+		&self,
+		_parent: &BlockId<Block>,
+		pre_hash: &H256,
+		_pre_digest: Option<&[u8]>,
+		seal: &RawSeal,
+		difficulty: U256,
+	) -> Result<bool, PowError<Block>> {
+		let seal = match Sha3Seal::decode(&mut &seal[..]) {
+			Ok(seal) => seal,
+			Err(_) => return Ok(false),
+		};
+
+		if seal.difficulty != difficulty || !seal.meets(difficulty) {
+			return Ok(false);
+		}
+
+		// Recompute the work so a seal cannot claim a hash it did not compute
+		Ok(Sha3Seal::compute(pre_hash, difficulty, seal.nonce) == seal)
+	}
+}
+
+/// Mines on the templates published by `handle` on one thread, trying every `step`-th nonce
+/// starting at `offset` so that threads never overlap. Returns once `stop` is set.
+pub fn mine<Block, Client, L, Proof>(
+	handle: MiningHandle<Block, Sha3Algorithm<Client>, L, Proof>,
+	offset: u64,
+	step: u64,
+	stop: Arc<AtomicBool>,
+) where
+	Block: BlockT<Hash = H256>,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: DifficultyApi<Block, U256>,
+	L: JustificationSyncLink<Block>,
+{
+	info!(target: "pow", "⛏️  Starting mining thread {}", offset);
+
+	let mut version = handle.version();
+	let mut nonce = U256::from(offset);
+
+	while !stop.load(Ordering::Relaxed) {
+		let Some(metadata) = handle.metadata() else {
+			std::thread::sleep(Duration::from_millis(500));
+			continue;
+		};
+
+		if handle.version() != version {
+			version = handle.version();
+			nonce = U256::from(offset);
+		}
+
+		for _ in 0..NONCES_PER_ROUND {
+			let seal = Sha3Seal::compute(&metadata.pre_hash, metadata.difficulty, nonce);
+			nonce = nonce.overflowing_add(U256::from(step)).0;
+
+			if seal.meets(metadata.difficulty) {
+				debug!(
+					target: "pow",
+					"Found seal for {:?} with nonce {}",
+					metadata.pre_hash, seal.nonce
+				);
+				if futures::executor::block_on(handle.submit(seal.encode())) {
+					// The template is used up, so wait for the next one instead of mining it again
+					let submitted = handle.version();
+					while handle.version() == submitted && !stop.load(Ordering::Relaxed) {
+						std::thread::sleep(Duration::from_millis(100));
+					}
+				} else {
+					warn!(
+						target: "pow",
+						"Mined block on {:?} was not imported",
+						metadata.best_hash
+					);
+				}
+				break;
+			}
+		}
+	}
+
+	info!(target: "pow", "⛏️  Stopping mining thread {}", offset);
+}
+
+/// Sets its flag when dropped, so mining threads stop along with whatever owns it.
+pub struct StopMining(pub Arc<AtomicBool>);
+
+impl Drop for StopMining {
+	fn drop(&mut self) {
+		self.0.store(true, Ordering::Relaxed);
+	}
+}
+
+/// Wraps `inner` in a block import that checks proof-of-work seals and picks the chain with the
+/// most accumulated difficulty.
+fn pow_block_import(
+	inner: BoxBlockImport<OpaqueBlock>,
+	client: Arc<FullClient>,
+	select_chain: FullSelectChain,
+) -> PowBlockImport<
+	OpaqueBlock,
+	BoxBlockImport<OpaqueBlock>,
+	FullClient,
+	FullSelectChain,
+	Sha3Algorithm<FullClient>,
+	impl CreateInherentDataProviders<OpaqueBlock, ()>,
+> {
+	PowBlockImport::new(
+		inner,
+		client.clone(),
+		Sha3Algorithm::new(client),
+		0,
+		select_chain,
+		move |_, ()| async move { Ok(sp_timestamp::InherentDataProvider::from_system_time()) },
+	)
+}
+
+/// Proof-of-work over [`Sha3Algorithm`], mining on `threads` CPU threads. With no threads the
+/// node only imports.
+pub struct PowEngine {
+	threads: usize,
+	/// Paid the rewards of the blocks we mine.
+	miner_account: Option<AccountId>,
+}
+
+impl ConsensusEngine for PowEngine {
+	fn parse(id: &str, cli: &Cli) -> Option<Result<Self, String>> {
+		let threads = if id == "pow" {
+			Ok(std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+		} else {
+			id.strip_prefix("pow-")?
+				.parse()
+				.map_err(|_| "invalid mining thread count".into())
+		};
+		Some(threads.map(|threads| Self { threads, miner_account: cli.miner_account.clone() }))
+	}
+
+	fn name(&self) -> &'static str {
+		"pow"
+	}
+
+	fn import_queue(
+		&self,
+		params: ImportQueueParams<'_>,
+	) -> Result<DefaultImportQueue<OpaqueBlock>, ServiceError> {
+		let block_import =
+			pow_block_import(params.block_import, params.client.clone(), params.select_chain);
+		Ok(sc_consensus_pow::import_queue(
+			Box::new(block_import),
+			None,
+			Sha3Algorithm::new(params.client),
+			&params.spawner,
+			params.registry,
+		)?)
+	}
+
+	fn start_authoring(self: Box<Self>, params: AuthoringParams<'_>) -> Result<(), ServiceError> {
+		let threads = self.threads;
+		if threads == 0 {
+			info!("Proof-of-work node running without mining threads");
+			return Ok(());
+		}
+
+		// Names the miner in a pre-runtime digest, so the runtime pays it the block reward
+		let pre_runtime = self.miner_account.as_ref().map(Encode::encode);
+		if pre_runtime.is_none() {
+			warn!("Mining without --miner-account: block rewards will not be paid");
+		}
+
+		let client = params.client;
+		let block_import =
+			pow_block_import(params.block_import, client.clone(), params.select_chain.clone());
+		let (mining_handle, mining_worker) = sc_consensus_pow::start_mining_worker(
+			Box::new(block_import),
+			client.clone(),
+			params.select_chain,
+			Sha3Algorithm::new(client),
+			params.proposer,
+			params.sync_service.clone(),
+			params.sync_service,
+			pre_runtime,
+			move |_, ()| async move { Ok(sp_timestamp::InherentDataProvider::from_system_time()) },
+			Duration::from_secs(10),
+			Duration::from_secs(10),
+		);
+
+		params.task_manager.spawn_essential_handle().spawn(
+			"pow-mining-worker",
+			Some("pow"),
+			mining_worker,
+		);
+
+		// Mining threads never yield, so they stop on a flag set when the task manager drops
+		// this task on shutdown
+		let stop = Arc::new(AtomicBool::new(false));
+		let stop_mining = StopMining(stop.clone());
+		params
+			.task_manager
+			.spawn_handle()
+			.spawn("pow-miner-stop", Some("pow"), async move {
+				let _stop_mining = stop_mining;
+				futures::future::pending::<()>().await
+			});
+
+		for thread in 0..threads {
+			let mining_handle = mining_handle.clone();
+			let stop = stop.clone();
+			params.task_manager.spawn_handle().spawn_blocking(
+				"pow-miner",
+				Some("pow"),
+				async move { mine(mining_handle, thread as u64, threads as u64, stop) },
+			);
+		}
+
+		Ok(())
+	}
+}
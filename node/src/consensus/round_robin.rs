@@ -1,164 +1,879 @@
-use super::basic_block_producer::BasicBlockProducer;
-use super::block_production::BlockProducer;
-use log::{debug, info, warn, trace};
+use super::{
+	basic_block_producer::BasicBlockProducer,
+	block_production::BlockProducer,
+	clock::{Clock, SystemClock},
+	control::{AuthoringHandle, AuthoringState},
+	engine::{AuthoringParams, ConsensusEngine, ImportQueueParams},
+	events::{ConsensusEvent, ConsensusEvents, TurnDecision},
+	fork_choice::RoundRobinBlockImport,
+	import_queue::{
+		import_queue, RoundRobinVerifier, DEFAULT_MAX_TIMESTAMP_DRIFT, MAX_TIMESTAMP_DRIFT,
+	},
+	keystore::{AuthorityKeystore, PqKeystore},
+	metrics::Metrics,
+};
+use crate::cli::Cli;
+use codec::{Decode, Encode};
+use futures::{
+	future::{self, Either},
+	lock::Mutex,
+	StreamExt,
+};
+use futures_timer::Delay;
+use jsonrpsee::{types::ErrorObjectOwned, RpcModule};
+use log::{error, info, trace, warn};
+use minimal_template_runtime::interface::OpaqueBlock;
 use polkadot_sdk::{
-    sc_client_api::{Backend, BlockBackend, BlockchainEvents, HeaderBackend},
-    sc_consensus::{BlockImport, BlockImportParams, ForkChoiceStrategy},
-    sp_api::{Core, ProvideRuntimeApi},
-    sp_consensus::{BlockOrigin, Error as ConsensusError},
-    sp_runtime::{
-        traits::{Block as BlockT, One},
-        Saturating,
-        SaturatedConversion,
-    },
-    sp_block_builder::BlockBuilder,
+	sc_client_api::{AuxStore, BlockchainEvents, HeaderBackend},
+	sc_consensus::{BlockImport, BlockImportParams, DefaultImportQueue, ImportResult},
+	sc_rpc_api::check_if_safe,
+	sc_service::{error::Error as ServiceError, Role},
+	sp_api::{ApiError, ProvideRuntimeApi},
+	sp_consensus::{BlockOrigin, Error as ConsensusError},
+	sp_panic_handler::AbortGuard,
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT, One},
+		Digest, DigestItem, SaturatedConversion,
+	},
+	sp_timestamp::{self, Timestamp},
+};
+use sp_consensus_round_robin::{
+	fallback_position, find_pre_digest, scheduled_author, AuthorityId, AuthorityIndex,
+	CompatibleDigestItem, PreDigest, RoundRobinApi, Slot, SlotDuration,
 };
-use std::{sync::Arc, time::Duration};
-
-pub struct RoundRobinConsensus<Block: BlockT, Client, BE> {
-    client: Arc<Client>,
-    block_import: Box<dyn BlockImport<Block, Error = ConsensusError> + Send>,
-    block_producer: BasicBlockProducer<Block, Client, BE>,
-    validator_id: u32,
-    total_validators: u32,
+use std::{
+	any::Any,
+	future::Future,
+	panic::{self, AssertUnwindSafe},
+	path::PathBuf,
+	pin::pin,
+	sync::Arc,
+	task::Poll,
+	time::Duration,
+};
+
+/// How long to wait before trying again after failing to build a block.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Auxiliary storage key under which the slot and parent of the last block we authored are kept.
+const LAST_AUTHORED_KEY: &[u8] = b"round_robin_last_authored";
+
+/// Loads the slot and parent of the last block we authored, so a restarted node does not author in
+/// that slot again.
+fn load_last_authored<Block: BlockT, Client: AuxStore>(
+	client: &Client,
+) -> Result<Option<(Slot, Block::Hash)>, String> {
+	let last_authored = client
+		.get_aux(LAST_AUTHORED_KEY)
+		.map_err(|e| format!("Could not load the last authored slot: {}", e))?;
+	last_authored
+		.map(|last_authored| Decode::decode(&mut &last_authored[..]))
+		.transpose()
+		.map_err(|e| format!("Corrupt last authored slot: {}", e))
+}
+
+/// The timestamp of a block built now with `inherent_digests`. With slots it is the start of the
+/// slot the pre-digest claims, as with Aura, so the timestamp falls in that slot however late in it
+/// the block is built; otherwise it is the current time.
+fn block_timestamp(inherent_digests: &Digest, slot_duration: Option<Duration>) -> Timestamp {
+	let now = Timestamp::current();
+	let slot = inherent_digests
+		.logs()
+		.iter()
+		.find_map(|item| item.as_round_robin_pre_digest())
+		.map(|pre_digest| pre_digest.slot);
+	match (slot, slot_duration) {
+		(Some(slot), Some(slot_duration)) =>
+			Timestamp::new((*slot).saturating_mul(slot_duration.as_millis() as u64)).min(now),
+		_ => now,
+	}
+}
+
+/// Polls `future` to completion, catching its panics. Panics unwind rather than exit the node, as
+/// they would by default.
+async fn catch_panic<F: Future>(future: F) -> Result<F::Output, Box<dyn Any + Send>> {
+	let mut future = pin!(future);
+	future::poll_fn(|cx| {
+		let _unwind = AbortGuard::force_unwind();
+		match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+			Ok(poll) => poll.map(Ok),
+			Err(panic) => Poll::Ready(Err(panic)),
+		}
+	})
+	.await
+}
+
+/// The timing of round-robin authoring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundRobinTiming {
+	/// The minimum time between the arrival of a block and building on it: the block time while
+	/// every validator authors on its turn.
+	pub block_time: Duration,
+	/// How long a validator may spend adding pool transactions to a block.
+	pub proposal_deadline: Duration,
+}
+
+impl RoundRobinTiming {
+	/// Timing for `block_time`, spending at most half of it on proposals.
+	pub fn new(block_time: Duration) -> Self {
+		Self { block_time, proposal_deadline: block_time / 2 }
+	}
+
+	/// Checks that a validator can build a block within its turn.
+	pub fn validate(&self) -> Result<(), String> {
+		if self.block_time.is_zero() {
+			return Err("the round-robin block time must be positive".into());
+		}
+		if self.proposal_deadline.is_zero() || self.proposal_deadline > self.block_time {
+			return Err(format!(
+				"the round-robin proposal deadline must be positive and at most the block time \
+				 ({:?})",
+				self.block_time
+			));
+		}
+		Ok(())
+	}
+
+	/// Checks that blocks built with this timing follow the timing of the chain, and that a
+	/// scheduled validator can author before others may take its turn.
+	pub fn validate_for(&self, chain: &ChainTiming) -> Result<(), String> {
+		if let Some(block_time) =
+			chain.block_time.filter(|block_time| self.block_time < *block_time)
+		{
+			return Err(format!(
+				"the round-robin block time ({:?}) is below the chain's ({:?})",
+				self.block_time, block_time
+			));
+		}
+		// With slots the next leader waits out the block time after a block built at the start of
+		// a slot, and must still build its own within the next slot
+		let turn = self.block_time + self.proposal_deadline;
+		if let Some(slot_duration) =
+			chain.slot_duration.filter(|slot_duration| turn > *slot_duration)
+		{
+			return Err(format!(
+				"the round-robin block time plus the proposal deadline ({:?}) must not exceed the \
+				 chain's slot duration ({:?}), or validators miss their slots",
+				turn, slot_duration
+			));
+		}
+		if let Some(timeout) = chain.missed_turn_timeout.filter(|timeout| *timeout <= turn) {
+			return Err(format!(
+				"the chain's missed-turn timeout ({:?}) must exceed the round-robin block time plus \
+				 the proposal deadline ({:?}), or validators take over turns that are not missed",
+				timeout, turn
+			));
+		}
+		Ok(())
+	}
+}
+
+impl Default for RoundRobinTiming {
+	fn default() -> Self {
+		Self::new(Duration::from_secs(1))
+	}
+}
+
+/// The timing a chain imposes on its round-robin validators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChainTiming {
+	/// The slot duration, if turns are given by slots.
+	pub slot_duration: Option<Duration>,
+	/// How long validators wait after a block before building on it, if the chain sets it.
+	pub block_time: Option<Duration>,
+	/// How long after a block the validator after the scheduled one may author in its place, if
+	/// missed turns are skipped.
+	pub missed_turn_timeout: Option<Duration>,
+}
+
+impl ChainTiming {
+	/// Reads the timing of the chain as of block `at`.
+	pub fn at<Block, Client>(client: &Client, at: Block::Hash) -> Result<Self, String>
+	where
+		Block: BlockT,
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: RoundRobinApi<Block>,
+	{
+		let runtime_api = client.runtime_api();
+		let read =
+			|e: ApiError| format!("Could not read the round-robin timing of the chain: {}", e);
+		Ok(Self {
+			slot_duration: runtime_api
+				.slot_duration(at)
+				.map_err(read)?
+				.map(|slot_duration| Duration::from_millis(slot_duration.as_millis())),
+			block_time: runtime_api.block_time(at).map_err(read)?.map(Duration::from_millis),
+			missed_turn_timeout: runtime_api
+				.missed_turn_timeout(at)
+				.map_err(read)?
+				.map(Duration::from_millis),
+		})
+	}
+}
+
+/// When a validator may author on a block.
+enum Turn {
+	/// Now, skipping this many authorities that missed their turn.
+	Now(u32),
+	/// Not now, but after this long if the authorities scheduled before it miss their turn.
+	Later(Option<Duration>),
 }
 
-impl<Block, Client, BE> RoundRobinConsensus<Block, Client, BE>
+pub struct RoundRobinConsensus<Block: BlockT, Client, P> {
+	client: Arc<Client>,
+	block_import: Box<dyn BlockImport<Block, Error = ConsensusError> + Send>,
+	block_producer: P,
+	keystore: AuthorityKeystore,
+	timing: RoundRobinTiming,
+	clock: Arc<dyn Clock>,
+	/// When the current best block arrived, since the Unix epoch.
+	best_arrived: Duration,
+	/// The slot and parent of the last block we authored, since sealing two blocks for a slot is
+	/// reported as equivocation. With slots we never author again in that slot or an earlier one,
+	/// even on another fork. With turns by height only blocks on the same parent equivocate, so we
+	/// may author at that height again after a reorganisation. Kept in auxiliary storage along
+	/// with the block, so it survives restarts.
+	last_authored: Option<(Slot, Block::Hash)>,
+	metrics: Option<Metrics>,
+	events: ConsensusEvents,
+	handle: AuthoringHandle,
+}
+
+impl<Block, Client, P> RoundRobinConsensus<Block, Client, P>
 where
-    Block: BlockT,
-    BE: Backend<Block> + Send + Sync,
-    Client: BlockBackend<Block> 
-        + BlockchainEvents<Block> 
-        + HeaderBackend<Block> 
-        + ProvideRuntimeApi<Block> 
-        + Send 
-        + Sync,
-    Client::Api: BlockBuilder<Block> + Core<Block>,
+	Block: BlockT,
+	P: BlockProducer<Block>,
+	Client: AuxStore
+		+ BlockchainEvents<Block>
+		+ HeaderBackend<Block>
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync,
+	Client::Api: RoundRobinApi<Block>,
 {
-    pub fn new(
-        client: Arc<Client>,
-        block_import: Box<dyn BlockImport<Block, Error = ConsensusError> + Send>,
-        validator_id: u32,
-        total_validators: u32,
-    ) -> Self {
-        let block_producer = BasicBlockProducer::new(client.clone());
-        Self {
-            client,
-            block_import,
-            block_producer,
-            validator_id,
-            total_validators,
-        }
-    }
-
-    pub async fn run(&mut self) {
-        info!("🚀 Starting round-robin consensus for validator {}", self.validator_id);
-        println!("🚀 Starting round-robin consensus for validator {}", self.validator_id);
-        
-        loop {
-            if let Err(e) = self.produce_block().await {
-                warn!("Failed to produce block: {:?}", e);
-            }
-            tokio::time::sleep(Duration::from_secs(1)).await;
-        }
-    }
-
-    async fn try_build_block(&mut self) -> Result<(), ConsensusError> {
-        let info = self.client.info();
-        let best_hash = info.best_hash;
-        let best_number = info.best_number;
-        
-        let next_validator = (best_number.saturated_into::<u32>() + 1) % self.total_validators;
-        
-        if next_validator != self.validator_id {
-            debug!(target: "round-robin", "Not our turn to produce block");
-            return Ok(());
-        }
-
-        info!(
-            target: "round-robin", 
-            "Building block {}", 
-            (best_number + One::one()).saturated_into::<u32>()
-        );
-
-        // Produce the new block
-        let block = self.block_producer
-            .produce_block(best_hash, best_number)
-            .await?;
-
-        // Import the produced block
-        let (header, body) = block.deconstruct();
-        let mut import_params = BlockImportParams::new(BlockOrigin::Own, header);
-        import_params.body = Some(body);
-        import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
-
-        self.block_import
-            .import_block(import_params)
-            .await
-            .map_err(|e| ConsensusError::Other(Box::new(e)))?;
-
-        info!(
-            target: "round-robin",
-            "Validator {} produced block at height {}",
-            self.validator_id,
-            (best_number + One::one()).saturated_into::<u32>()
-        );
-
-        Ok(())
-    }
-
-    async fn produce_block(&mut self) -> Result<(), ConsensusError> {
-        let best_header = self.client.info().best_hash;
-        let best_number = self.client.info().best_number;
-
-        // Check if it's our turn
-        let slot = best_number.saturating_add(1u32.into()) % self.total_validators.into();
-        
-        let msg = format!("🔄 Round-Robin: Block #{} | Slot {} | Our ID {} | Total Validators {}", 
-            best_number + 1u32.into(),
-            slot,
-            self.validator_id,
-            self.total_validators
-        );
-        println!("{}", msg);
-        info!("{}", msg);
-
-        if slot == self.validator_id.into() {
-            let msg = format!("🎯 Our turn! Validator {} producing block #{}", 
-                self.validator_id,
-                best_number + 1u32.into()
-            );
-            println!("{}", msg);
-            info!("{}", msg);
-            
-            let block = self.block_producer
-                .produce_block(best_header, best_number)
-                .await?;
-
-            println!("✅ SUCCESS: Validator {} produced block #{} ({})", 
-                self.validator_id,
-                best_number + 1u32.into(),
-                block.hash()
-            );
-
-            // Import the block
-            let (header, body) = block.deconstruct();
-            let mut import_params = BlockImportParams::new(BlockOrigin::Own, header);
-            import_params.body = Some(body);
-            import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
-
-            match self.block_import.import_block(import_params).await {
-                Ok(_) => println!("📥 Block #{} successfully imported", best_number + 1u32.into()),
-                Err(e) => println!("❌ Failed to import block #{}: {:?}", best_number + 1u32.into(), e),
-            }
-        } else {
-            println!("⏳ Not our turn. Validator {} waiting (slot {} belongs to validator {})", 
-                self.validator_id,
-                slot,
-                slot
-            );
-        }
-
-        Ok(())
-    }
+	pub fn new(
+		client: Arc<Client>,
+		block_import: Box<dyn BlockImport<Block, Error = ConsensusError> + Send>,
+		block_producer: P,
+		keystore: AuthorityKeystore,
+		timing: RoundRobinTiming,
+	) -> Self {
+		let last_authored = load_last_authored::<Block, _>(&*client).unwrap_or_else(|e| {
+			warn!(target: "round-robin", "{}; authoring in any slot", e);
+			None
+		});
+		Self {
+			client,
+			block_import,
+			block_producer,
+			keystore,
+			timing,
+			clock: Arc::new(SystemClock),
+			best_arrived: SystemClock.now(),
+			last_authored,
+			metrics: None,
+			events: ConsensusEvents::default(),
+			handle: AuthoringHandle::default(),
+		}
+	}
+
+	/// Records authoring in `metrics`.
+	pub fn with_metrics(mut self, metrics: Option<Metrics>) -> Self {
+		self.metrics = metrics;
+		self
+	}
+
+	/// Reports what authoring does to `events` rather than only to the log.
+	pub fn with_events(mut self, events: ConsensusEvents) -> Self {
+		self.events = events;
+		self
+	}
+
+	/// Lets `handle` pause, resume and stop authoring.
+	pub fn with_handle(mut self, handle: AuthoringHandle) -> Self {
+		self.handle = handle;
+		self
+	}
+
+	/// Reads the time from `clock` rather than the system clock.
+	pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+		self.best_arrived = clock.now();
+		self.clock = clock;
+		self
+	}
+
+	/// Builds authoring anew from the parts of this one, as after a restart: nothing of the state
+	/// of its run carries over, and the last authored slot is reloaded from auxiliary storage.
+	fn rebuild(self) -> Self {
+		Self::new(self.client, self.block_import, self.block_producer, self.keystore, self.timing)
+			.with_clock(self.clock)
+			.with_metrics(self.metrics)
+			.with_events(self.events)
+			.with_handle(self.handle)
+	}
+
+	/// Notes the arrival of a new best block.
+	fn on_new_best(&mut self, header: &Block::Header) {
+		self.best_arrived = self.clock.now();
+		if let (Some(metrics), Some(pre_digest)) = (&self.metrics, find_pre_digest(header)) {
+			metrics.turns_missed.inc_by(pre_digest.skipped as u64);
+		}
+	}
+
+	/// Authors blocks on our turns until stopped through the handle or the import notification
+	/// stream ends.
+	///
+	/// A turn is checked for whenever a new best block arrives, once the block time has passed
+	/// since its arrival, when the missed-turn timeout lets us author in place of the scheduled
+	/// authority, and with slots at the start of every slot. In between the task sleeps. While
+	/// paused it only follows the best block.
+	pub async fn run(&mut self) {
+		self.events.emit(ConsensusEvent::Started);
+
+		let mut best_blocks = self
+			.client
+			.import_notification_stream()
+			.filter(|notification| future::ready(notification.is_new_best));
+		let mut state_changes = self.handle.state_changes();
+		let mut state = AuthoringState::Running;
+
+		loop {
+			let previous = std::mem::replace(&mut state, self.handle.state());
+			match (previous, state) {
+				(_, AuthoringState::Stopped) => break,
+				(AuthoringState::Running, AuthoringState::Paused) =>
+					self.events.emit(ConsensusEvent::Paused),
+				(AuthoringState::Paused, AuthoringState::Running) =>
+					self.events.emit(ConsensusEvent::Resumed),
+				_ => {},
+			}
+			if state == AuthoringState::Paused {
+				match future::select(best_blocks.next(), state_changes.next()).await {
+					Either::Left((Some(notification), _)) => self.on_new_best(&notification.header),
+					Either::Left((None, _)) => break,
+					Either::Right(_) => {},
+				}
+				continue;
+			}
+
+			// Wait out the block time, starting over if another best block arrives or the state
+			// changes meanwhile
+			let ready_at = self.best_arrived + self.timing.block_time;
+			let block_time = self.clock.sleep(ready_at.saturating_sub(self.clock.now()));
+			let woken = future::select(block_time, state_changes.next());
+			match future::select(best_blocks.next(), woken).await {
+				Either::Left((Some(notification), _)) => {
+					self.on_new_best(&notification.header);
+					continue;
+				},
+				Either::Left((None, _)) => break,
+				Either::Right((Either::Left(_), _)) => {},
+				Either::Right((Either::Right(_), _)) => continue,
+			}
+
+			let idle = match self.produce_block().await {
+				Ok(Some(fallback)) => Ok(Some(fallback)),
+				Ok(None) => self.until_next_slot(),
+				Err(e) => Err(e),
+			};
+			let idle = idle.unwrap_or_else(|e| {
+				warn!("Failed to produce block: {:?}", e);
+				Some(RETRY_INTERVAL)
+			});
+
+			// Sleep until another best block arrives, our turn comes by skipping authorities that
+			// missed theirs, with slots the next slot starts, or the state changes
+			let wake = match idle {
+				Some(idle) => Either::Left(self.clock.sleep(idle)),
+				None => Either::Right(future::pending()),
+			};
+			let woken = future::select(wake, state_changes.next());
+			match future::select(best_blocks.next(), woken).await {
+				Either::Left((Some(notification), _)) => self.on_new_best(&notification.header),
+				Either::Left((None, _)) => break,
+				Either::Right(_) => {},
+			}
+		}
+
+		if state != AuthoringState::Stopped {
+			warn!(target: "round-robin", "Import notification stream ended; stopping authoring");
+		}
+		self.events.emit(ConsensusEvent::Stopped);
+	}
+
+	/// Fetches the authority set at `parent_hash`.
+	fn authorities(&self, parent_hash: Block::Hash) -> Result<Vec<AuthorityId>, ConsensusError> {
+		self.client
+			.runtime_api()
+			.authorities(parent_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))
+	}
+
+	/// Fetches the authoring schedule for a block in `slot` on top of `parent_hash`.
+	fn schedule(
+		&self,
+		parent_hash: Block::Hash,
+		slot: Slot,
+	) -> Result<Vec<AuthorityIndex>, ConsensusError> {
+		self.client
+			.runtime_api()
+			.schedule(parent_hash, slot)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))
+	}
+
+	/// Returns our index in `authorities`: the first authority whose key is in our keystore.
+	pub fn own_index(&self, authorities: &[AuthorityId]) -> Option<AuthorityIndex> {
+		authorities
+			.iter()
+			.position(|authority| self.keystore.has_key(authority))
+			.map(|index| index as AuthorityIndex)
+	}
+
+	/// Returns the slot a block built on top of `parent` would claim, or `None` if a block was
+	/// already built on `parent` in the current slot.
+	///
+	/// Without a slot duration the slot of a block is its height. Otherwise it is derived from the
+	/// current time and must be later than the parent's.
+	fn next_slot(
+		&self,
+		parent: &Block::Header,
+		slot_duration: Option<SlotDuration>,
+	) -> Result<Option<Slot>, ConsensusError> {
+		let Some(slot_duration) = slot_duration else {
+			let number = *parent.number() + One::one();
+			return Ok(Some(Slot::from(number.saturated_into::<u64>())));
+		};
+
+		let slot = Slot::from_timestamp(self.clock.timestamp(), slot_duration);
+		let parent_slot = find_pre_digest(parent).map(|d| d.slot).unwrap_or_default();
+		Ok((slot > parent_slot).then_some(slot))
+	}
+
+	/// Returns the time until the next slot starts, or `None` if turns are given by height.
+	fn until_next_slot(&self) -> Result<Option<Duration>, ConsensusError> {
+		let slot_duration = self
+			.client
+			.runtime_api()
+			.slot_duration(self.client.info().best_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
+
+		Ok(slot_duration.map(|slot_duration| {
+			let slot_duration = slot_duration.as_millis();
+			let now = self.clock.timestamp().as_millis();
+			Duration::from_millis(slot_duration - now % slot_duration)
+		}))
+	}
+
+	fn best_header(&self) -> Result<Block::Header, ConsensusError> {
+		let best_hash = self.client.info().best_hash;
+		self.client
+			.header(best_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?
+			.ok_or(ConsensusError::ChainLookup("Best header not found".into()))
+	}
+
+	/// Builds a block on top of `parent` claiming `pre_digest` as `authority`, seals it and imports
+	/// it.
+	async fn build_and_import(
+		&mut self,
+		parent: &Block::Header,
+		pre_digest: PreDigest,
+		authority: &AuthorityId,
+	) -> Result<Block::Hash, ConsensusError> {
+		let inherent_digests =
+			Digest { logs: vec![DigestItem::round_robin_pre_digest(pre_digest.clone())] };
+
+		let block = self.block_producer.produce_block(parent.hash(), inherent_digests).await?;
+
+		// Sign the pre-seal header hash and attach the signature as a seal
+		let (header, body) = block.deconstruct();
+		let pre_hash = header.hash();
+		let signature = self.keystore.sign(authority, pre_hash.as_ref())?;
+		let seal = DigestItem::round_robin_seal(signature);
+
+		let mut post_header = header.clone();
+		post_header.digest_mut().push(seal.clone());
+		let post_hash = post_header.hash();
+		self.events.emit(ConsensusEvent::BlockAuthored {
+			number: (*header.number()).saturated_into::<u64>(),
+			hash: format!("{:?}", post_hash),
+			authority_index: pre_digest.authority_index,
+			skipped: pre_digest.skipped,
+		});
+
+		let mut import_params = BlockImportParams::new(BlockOrigin::Own, header);
+		import_params.post_digests.push(seal);
+		import_params.post_hash = Some(post_hash);
+		import_params.body = Some(body);
+		import_params
+			.auxiliary
+			.push((LAST_AUTHORED_KEY.to_vec(), Some((pre_digest.slot, parent.hash()).encode())));
+
+		let imported = self.block_import.import_block(import_params).await;
+		if let Some(metrics) = &self.metrics {
+			if !matches!(imported, Ok(ImportResult::Imported(_))) {
+				metrics.import_failures.inc();
+			}
+		}
+		imported.map_err(|e| ConsensusError::Other(Box::new(e)))?;
+
+		Ok(post_hash)
+	}
+
+	/// Returns when we may author on `parent_hash` in `slot` as authority `own_index`, following
+	/// `schedule`.
+	fn turn(
+		&self,
+		parent_hash: Block::Hash,
+		slot: Slot,
+		own_index: AuthorityIndex,
+		schedule: &[AuthorityIndex],
+	) -> Result<Turn, ConsensusError> {
+		// Authorities of weight zero never author, not even in place of others
+		let Some(skipped) = fallback_position(slot, own_index, schedule) else {
+			return Ok(Turn::Later(None));
+		};
+		if skipped == 0 {
+			return Ok(Turn::Now(0));
+		}
+
+		let fallback_timestamp = self
+			.client
+			.runtime_api()
+			.fallback_timestamp(parent_hash, skipped)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
+		let Some(fallback_timestamp) = fallback_timestamp else {
+			return Ok(Turn::Later(None));
+		};
+
+		let now = self.clock.timestamp().as_millis();
+		Ok(if fallback_timestamp > now {
+			Turn::Later(Some(Duration::from_millis(fallback_timestamp - now)))
+		} else {
+			Turn::Now(skipped)
+		})
+	}
+
+	/// Authors a block on the best block if it is our turn, or if the authorities before us missed
+	/// theirs.
+	///
+	/// Returns how long until we may author in place of authorities that miss their turn, if the
+	/// chain allows it. Fails if building or importing our block fails, so that it is retried.
+	async fn produce_block(&mut self) -> Result<Option<Duration>, ConsensusError> {
+		let best_header = self.best_header()?;
+		let best_hash = best_header.hash();
+		let best_number = *best_header.number();
+
+		let slot_duration = self
+			.client
+			.runtime_api()
+			.slot_duration(best_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?;
+		let Some(slot) = self.next_slot(&best_header, slot_duration)? else {
+			trace!(target: "round-robin", "Already built on #{} in the current slot", best_number);
+			return Ok(None);
+		};
+		let already_authored = self.last_authored.is_some_and(|(last_slot, last_parent)| {
+			if slot_duration.is_some() {
+				slot <= last_slot
+			} else {
+				slot == last_slot && last_parent == best_hash
+			}
+		});
+		if already_authored {
+			trace!(target: "round-robin", "Already authored in slot {}", *slot);
+			return Ok(None);
+		}
+
+		// Check if it's our turn
+		let authorities = self.authorities(best_hash)?;
+		let schedule = self.schedule(best_hash, slot)?;
+		let leader = scheduled_author(slot, &schedule)
+			.filter(|leader| (*leader as usize) < authorities.len())
+			.ok_or(ConsensusError::InvalidAuthoritiesSet)?;
+		if let Some(metrics) = &self.metrics {
+			metrics.current_leader.set(leader as u64);
+		}
+		let own_index = self.own_index(&authorities);
+		let number = (best_number + One::one()).saturated_into::<u64>();
+
+		let turn = match own_index {
+			Some(own_index) => self.turn(best_hash, slot, own_index, &schedule)?,
+			None => Turn::Later(None),
+		};
+		let decision = match (own_index, &turn) {
+			(None, _) => TurnDecision::NotAuthority,
+			(Some(_), Turn::Now(skipped)) => TurnDecision::Author { skipped: *skipped },
+			(Some(_), Turn::Later(_)) => TurnDecision::Wait,
+		};
+		self.events.emit(ConsensusEvent::TurnEvaluated {
+			number,
+			slot: *slot,
+			leader,
+			own_index,
+			authorities: authorities.len(),
+			decision,
+		});
+
+		let Some(own_index) = own_index else {
+			return Ok(None);
+		};
+		let skipped = match turn {
+			Turn::Now(skipped) => skipped,
+			Turn::Later(fallback) => return Ok(fallback),
+		};
+
+		let pre_digest = PreDigest { authority_index: own_index, slot, skipped };
+		let authority = authorities[own_index as usize].clone();
+		let timer = self
+			.metrics
+			.as_ref()
+			.map(|metrics| metrics.block_production_duration.start_timer());
+		let result = self.build_and_import(&best_header, pre_digest, &authority).await;
+		drop(timer);
+		if let (Ok(_), Some(metrics)) = (&result, &self.metrics) {
+			metrics.blocks_authored.inc();
+			metrics.last_authored_timestamp.set(self.clock.now().as_secs());
+		}
+
+		match result {
+			Ok(hash) => {
+				self.last_authored = Some((slot, best_hash));
+				self.events
+					.emit(ConsensusEvent::ImportSucceeded { number, hash: format!("{:?}", hash) });
+			},
+			Err(e) => {
+				self.events.emit(ConsensusEvent::ImportFailed { number, error: e.to_string() });
+				return Err(e);
+			},
+		}
+
+		Ok(None)
+	}
+}
+
+/// Round-robin authoring among the on-chain authority set, with blocks finalized by GRANDPA.
+pub struct RoundRobinEngine {
+	/// Our index in the authority set and its size, if given with
+	/// `--consensus round-robin-<id>-<total>`, to check against the on-chain set at startup.
+	expected_position: Option<(u32, u32)>,
+	timing: RoundRobinTiming,
+	/// Where to append consensus events as JSON lines, if anywhere.
+	events_path: Option<PathBuf>,
+	/// How far ahead of our clock the timestamp of an imported block may be.
+	max_timestamp_drift: Duration,
+	authoring: AuthoringHandle,
+	/// Shared by the block imports of the import queue and authoring.
+	import_lock: Arc<Mutex<()>>,
+}
+
+impl ConsensusEngine for RoundRobinEngine {
+	fn parse(id: &str, cli: &Cli) -> Option<Result<Self, String>> {
+		let params = match id.strip_prefix("round-robin")? {
+			"" => None,
+			params => Some(params.strip_prefix('-')?),
+		};
+
+		let engine = || -> Result<Self, String> {
+			let position = params
+				.map(|params| -> Result<(u32, u32), String> {
+					let (validator_id, total_validators) = params.split_once('-').ok_or(
+						"round-robin-<id>-<total> requires validator-id and total-validators",
+					)?;
+					Ok((
+						validator_id.parse().map_err(|_| "invalid validator id")?,
+						total_validators.parse().map_err(|_| "invalid total validators")?,
+					))
+				})
+				.transpose()?;
+			let flag_position = cli.validator_id.zip(cli.total_validators);
+			let expected_position = match (position, flag_position) {
+				(Some(position), Some(flag_position)) if position != flag_position =>
+					return Err("--validator-id and --total-validators conflict with --consensus; \
+						use --consensus round-robin-<id>-<total> alone"
+						.into()),
+				(position, flag_position) => position.or(flag_position),
+			};
+
+			let mut timing = match cli.block_time {
+				Some(block_time) => RoundRobinTiming::new(Duration::from_millis(block_time)),
+				None => RoundRobinTiming::default(),
+			};
+			if let Some(proposal_deadline) = cli.proposal_deadline {
+				timing.proposal_deadline = Duration::from_millis(proposal_deadline);
+			}
+			timing.validate()?;
+			let max_timestamp_drift = cli
+				.max_timestamp_drift
+				.map_or(DEFAULT_MAX_TIMESTAMP_DRIFT, Duration::from_millis);
+			if max_timestamp_drift > MAX_TIMESTAMP_DRIFT {
+				return Err(format!(
+					"--max-timestamp-drift may not exceed {}ms, beyond which the runtime rejects \
+					 timestamps anyway",
+					MAX_TIMESTAMP_DRIFT.as_millis()
+				));
+			}
+
+			Ok(Self {
+				expected_position,
+				timing,
+				events_path: cli.consensus_events.clone(),
+				max_timestamp_drift,
+				authoring: AuthoringHandle::default(),
+				import_lock: Arc::default(),
+			})
+		};
+		Some(engine())
+	}
+
+	fn name(&self) -> &'static str {
+		"round-robin"
+	}
+
+	fn grandpa_finality(&self) -> bool {
+		true
+	}
+
+	fn import_queue(
+		&self,
+		params: ImportQueueParams<'_>,
+	) -> Result<DefaultImportQueue<OpaqueBlock>, ServiceError> {
+		let verifier = RoundRobinVerifier::new(
+			params.client.clone(),
+			params.offchain_tx_pool_factory,
+			move |_, ()| async move { Ok(sp_timestamp::InherentDataProvider::from_system_time()) },
+		)
+		.with_max_timestamp_drift(self.max_timestamp_drift);
+		let block_import = RoundRobinBlockImport::new(params.block_import, params.client)
+			.with_import_lock(self.import_lock.clone());
+		Ok(import_queue(
+			verifier,
+			block_import,
+			params.justification_import,
+			&params.spawner,
+			params.registry,
+		))
+	}
+
+	fn rpc_extensions(&self, role: &Role) -> Result<Option<RpcModule<()>>, ServiceError> {
+		// Only validators author, so only they serve the authoring state
+		if !role.is_authority() {
+			return Ok(None);
+		}
+
+		let mut module = RpcModule::new(self.authoring.clone());
+		module
+			.register_method("roundRobin_authoringState", |_, authoring, _| {
+				Ok::<_, ErrorObjectOwned>(authoring.state())
+			})
+			.map_err(|e| ServiceError::Other(e.to_string()))?;
+		// Pausing and resuming are unsafe, so only served locally unless `--rpc-methods unsafe`
+		module
+			.register_method("roundRobin_pauseAuthoring", |_, authoring, extensions| {
+				check_if_safe(extensions)?;
+				Ok::<_, ErrorObjectOwned>(authoring.pause())
+			})
+			.map_err(|e| ServiceError::Other(e.to_string()))?;
+		module
+			.register_method("roundRobin_resumeAuthoring", |_, authoring, extensions| {
+				check_if_safe(extensions)?;
+				Ok::<_, ErrorObjectOwned>(authoring.resume())
+			})
+			.map_err(|e| ServiceError::Other(e.to_string()))?;
+
+		let mut rpc = RpcModule::new(());
+		rpc.merge(module).map_err(|e| ServiceError::Other(e.to_string()))?;
+		Ok(Some(rpc))
+	}
+
+	fn authoring_handle(&self) -> Option<AuthoringHandle> {
+		Some(self.authoring.clone())
+	}
+
+	fn start_authoring(self: Box<Self>, params: AuthoringParams<'_>) -> Result<(), ServiceError> {
+		if !params.role.is_authority() {
+			info!("Round-robin node running without --validator: importing blocks only");
+			return Ok(());
+		}
+
+		let client = params.client;
+		let chain_timing = ChainTiming::at::<OpaqueBlock, _>(&*client, client.info().best_hash)
+			.map_err(ServiceError::Other)?;
+		self.timing.validate_for(&chain_timing).map_err(ServiceError::Other)?;
+
+		let pq_keystore = PqKeystore::open(params.keystore_path.as_deref())?;
+		if let Some(seed) = &params.dev_key_seed {
+			pq_keystore.insert(seed)?;
+		}
+		let keystore = AuthorityKeystore::new(params.keystore, Arc::new(pq_keystore));
+
+		let block_producer = BasicBlockProducer::new(
+			client.clone(),
+			params.proposer,
+			// The slot duration is only set at genesis
+			move |_, inherent_digests: Digest| async move {
+				let timestamp = block_timestamp(&inherent_digests, chain_timing.slot_duration);
+				Ok(sp_timestamp::InherentDataProvider::new(timestamp))
+			},
+			self.timing.proposal_deadline,
+		);
+		let metrics = params.registry.as_ref().map(Metrics::register).transpose()?;
+		let events = ConsensusEvents::new(params.telemetry, self.events_path.as_deref())?;
+		let block_import = RoundRobinBlockImport::new(params.block_import, client.clone())
+			.with_import_lock(self.import_lock.clone());
+		let round_robin = RoundRobinConsensus::new(
+			client.clone(),
+			Box::new(block_import),
+			block_producer,
+			keystore,
+			self.timing,
+		)
+		.with_metrics(metrics)
+		.with_events(events)
+		.with_handle(self.authoring.clone());
+
+		// Our turn is given by the on-chain authority set; the CLI values only serve as a check
+		if let Some((validator_id, total_validators)) = self.expected_position {
+			let authorities = client
+				.runtime_api()
+				.authorities(client.info().best_hash)
+				.map_err(|e| ServiceError::Other(e.to_string()))?;
+			let own_index = round_robin.own_index(&authorities);
+			if own_index != Some(validator_id) || authorities.len() != total_validators as usize {
+				return Err(ServiceError::Other(format!(
+					"Configured as validator {} of {}, but the on-chain authority set makes us \
+					 validator {:?} of {}; use --consensus round-robin to follow the on-chain set",
+					validator_id,
+					total_validators,
+					own_index,
+					authorities.len(),
+				)));
+			}
+		}
+
+		// Not an essential task: stopping authoring through its handle, or a panic in it, leaves
+		// the node running. A panic restarts authoring, rebuilt from its parts, unless it was
+		// stopped.
+		let authoring = self.authoring;
+		params.task_manager.spawn_handle().spawn_blocking(
+			"round-robin-consensus",
+			None,
+			async move {
+				let mut round_robin = round_robin;
+				while catch_panic(round_robin.run()).await.is_err() &&
+					authoring.state() != AuthoringState::Stopped
+				{
+					error!(
+						target: "round-robin",
+						"Authoring panicked; restarting in {:?}",
+						RETRY_INTERVAL
+					);
+					Delay::new(RETRY_INTERVAL).await;
+					round_robin = round_robin.rebuild();
+				}
+			},
+		);
+
+		Ok(())
+	}
 }
@@ -15,8 +15,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+// `sc_service::Error`, which the service builders fail with, is large
+#![allow(clippy::result_large_err)]
+
 pub mod chain_spec;
 pub(crate) mod cli;
+pub mod command;
+pub mod consensus;
 pub mod rpc;
 pub mod service;
-pub mod consensus;
@@ -17,14 +17,9 @@
 
 //! Substrate Node Template CLI library.
 #![warn(missing_docs)]
-
-mod chain_spec;
-mod cli;
-mod command;
-mod rpc;
-mod service;
-mod consensus;
+// `sc_cli::Error`, which the node fails with, is large
+#![allow(clippy::result_large_err)]
 
 fn main() -> polkadot_sdk::sc_cli::Result<()> {
-	command::run()
+	minimal_template_node::command::run()
 }
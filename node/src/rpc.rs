@@ -25,24 +25,48 @@
 use jsonrpsee::RpcModule;
 use minimal_template_runtime::interface::{AccountId, Nonce, OpaqueBlock};
 use polkadot_sdk::{
+	sc_consensus_grandpa::{
+		FinalityProofProvider, GrandpaJustificationStream, SharedAuthoritySet, SharedVoterState,
+	},
+	sc_rpc::SubscriptionTaskExecutor,
 	sc_transaction_pool_api::TransactionPool,
 	sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata},
+	sp_runtime::traits::{Block as BlockT, NumberFor},
 	*,
 };
 use std::sync::Arc;
 
+/// Dependencies of the GRANDPA finality RPC.
+pub struct GrandpaDeps<B> {
+	/// Voting round info.
+	pub shared_voter_state: SharedVoterState,
+	/// Authority set info.
+	pub shared_authority_set:
+		SharedAuthoritySet<<OpaqueBlock as BlockT>::Hash, NumberFor<OpaqueBlock>>,
+	/// Receives notifications about justification events from GRANDPA.
+	pub justification_stream: GrandpaJustificationStream<OpaqueBlock>,
+	/// Executor to drive the subscription manager in the GRANDPA RPC handler.
+	pub subscription_executor: SubscriptionTaskExecutor,
+	/// Finality proof provider.
+	pub finality_provider: Arc<FinalityProofProvider<B, OpaqueBlock>>,
+}
+
 /// Full client dependencies.
-pub struct FullDeps<C, P> {
+pub struct FullDeps<C, P, B> {
 	/// The client instance to use.
 	pub client: Arc<C>,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
+	/// GRANDPA dependencies, if the chain is finalized by GRANDPA.
+	pub grandpa: Option<GrandpaDeps<B>>,
+	/// RPC methods of the consensus engine, if it has any.
+	pub engine: Option<RpcModule<()>>,
 }
 
 #[docify::export]
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(
-	deps: FullDeps<C, P>,
+pub fn create_full<C, P, B>(
+	deps: FullDeps<C, P, B>,
 ) -> Result<RpcModule<()>, Box<dyn std::error::Error + Send + Sync>>
 where
 	C: Send
@@ -55,12 +79,41 @@ where
 	C::Api: sp_block_builder::BlockBuilder<OpaqueBlock>,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<OpaqueBlock, AccountId, Nonce>,
 	P: TransactionPool + 'static,
+	B: sc_client_api::Backend<OpaqueBlock> + Send + Sync + 'static,
+	B::State: sc_client_api::StateBackend<sp_runtime::traits::HashingFor<OpaqueBlock>>,
 {
-	use polkadot_sdk::substrate_frame_rpc_system::{System, SystemApiServer};
+	use polkadot_sdk::{
+		sc_consensus_grandpa_rpc::{Grandpa, GrandpaApiServer},
+		substrate_frame_rpc_system::{System, SystemApiServer},
+	};
 	let mut module = RpcModule::new(());
-	let FullDeps { client, pool } = deps;
+	let FullDeps { client, pool, grandpa, engine } = deps;
 
 	module.merge(System::new(client.clone(), pool.clone()).into_rpc())?;
 
+	if let Some(GrandpaDeps {
+		shared_voter_state,
+		shared_authority_set,
+		justification_stream,
+		subscription_executor,
+		finality_provider,
+	}) = grandpa
+	{
+		module.merge(
+			Grandpa::new(
+				subscription_executor,
+				shared_authority_set,
+				shared_voter_state,
+				justification_stream,
+				finality_provider,
+			)
+			.into_rpc(),
+		)?;
+	}
+
+	if let Some(engine) = engine {
+		module.merge(engine)?;
+	}
+
 	Ok(module)
 }
@@ -15,27 +15,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::consensus::{AuthoringHandle, AuthoringParams, ConsensusEngine, ImportQueueParams};
 use futures::FutureExt;
 use minimal_template_runtime::{interface::OpaqueBlock as Block, RuntimeApi};
-use super::consensus::RoundRobinConsensus;
 use polkadot_sdk::{
+	sc_client_api::{Backend, BlockBackend},
+	sc_consensus::BoxBlockImport,
 	sc_executor::WasmExecutor,
 	sc_service::{error::Error as ServiceError, Configuration, TaskManager},
 	sc_telemetry::{Telemetry, TelemetryWorker},
 	sc_transaction_pool_api::OffchainTransactionPoolFactory,
 	sp_runtime::traits::Block as BlockT,
-	sp_consensus::Error as ConsensusError,
-	sc_consensus::BlockImport,
-	sc_client_api::Backend,
-	sc_service::TFullBackend,
-	sc_client_db::Backend as DatabaseBackend,
 	*,
 };
-use std::sync::Arc;
-
-use crate::cli::Consensus;
-use crate::cli::Cli;
-use log::info;
+use std::{sync::Arc, time::Duration};
 
 type HostFunctions = sp_io::SubstrateHostFunctions;
 
@@ -43,8 +36,17 @@ type HostFunctions = sp_io::SubstrateHostFunctions;
 pub(crate) type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, WasmExecutor<HostFunctions>>;
 
-type FullBackend = sc_service::TFullBackend<Block>;
-type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+pub(crate) type FullBackend = sc_service::TFullBackend<Block>;
+pub(crate) type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+pub(crate) type FullPool = sc_transaction_pool::FullPool<Block, FullClient>;
+
+type GrandpaBlockImport =
+	sc_consensus_grandpa::GrandpaBlockImport<FullBackend, Block, FullClient, FullSelectChain>;
+type GrandpaLinkHalf = sc_consensus_grandpa::LinkHalf<Block, FullClient, FullSelectChain>;
+
+/// How often (in blocks) GRANDPA justifications are generated and stored, even without an
+/// authority set change.
+const GRANDPA_JUSTIFICATION_PERIOD: u32 = 512;
 
 /// Assembly of PartialComponents (enough to run chain ops subcommands)
 pub type Service = sc_service::PartialComponents<
@@ -52,11 +54,33 @@ pub type Service = sc_service::PartialComponents<
 	FullBackend,
 	FullSelectChain,
 	sc_consensus::DefaultImportQueue<Block>,
-	sc_transaction_pool::FullPool<Block, FullClient>,
-	Option<Telemetry>,
+	FullPool,
+	Other,
 >;
 
-pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
+/// The components of [`Service`] not common to every node.
+pub struct Other {
+	pub telemetry: Option<Telemetry>,
+	/// GRANDPA's block import and link, for chains of engines finalized by GRANDPA.
+	pub grandpa: Option<(GrandpaBlockImport, GrandpaLinkHalf)>,
+}
+
+/// Imports blocks into the client, through GRANDPA's block import if the chain is finalized by
+/// GRANDPA, which tracks authority set changes.
+fn block_import(
+	client: &Arc<FullClient>,
+	grandpa: &Option<(GrandpaBlockImport, GrandpaLinkHalf)>,
+) -> BoxBlockImport<Block> {
+	match grandpa {
+		Some((grandpa_block_import, _)) => Box::new(grandpa_block_import.clone()),
+		None => Box::new(client.clone()),
+	}
+}
+
+pub fn new_partial(
+	config: &Configuration,
+	engine: &dyn ConsensusEngine,
+) -> Result<Service, ServiceError> {
 	let telemetry = config
 		.telemetry_endpoints
 		.clone()
@@ -93,11 +117,29 @@ pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
 		client.clone(),
 	);
 
-	let import_queue = sc_consensus_manual_seal::import_queue(
-		Box::new(client.clone()),
-		&task_manager.spawn_essential_handle(),
-		config.prometheus_registry(),
-	);
+	let grandpa = if engine.grandpa_finality() {
+		Some(sc_consensus_grandpa::block_import(
+			client.clone(),
+			GRANDPA_JUSTIFICATION_PERIOD,
+			&client,
+			select_chain.clone(),
+			telemetry.as_ref().map(|x| x.handle()),
+		)?)
+	} else {
+		None
+	};
+
+	let import_queue = engine.import_queue(ImportQueueParams {
+		client: client.clone(),
+		select_chain: select_chain.clone(),
+		block_import: block_import(&client, &grandpa),
+		justification_import: grandpa
+			.as_ref()
+			.map(|(grandpa_block_import, _)| Box::new(grandpa_block_import.clone()) as Box<_>),
+		offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(transaction_pool.clone()),
+		spawner: Box::new(task_manager.spawn_essential_handle()),
+		registry: config.prometheus_registry(),
+	})?;
 
 	Ok(sc_service::PartialComponents {
 		client,
@@ -107,15 +149,16 @@ pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (telemetry),
+		other: Other { telemetry, grandpa },
 	})
 }
 
-/// Builds a new service for a full client.
+/// Builds a new service for a full client. Returns its task manager and, if the engine has one,
+/// the handle pausing, resuming and stopping its authoring task.
 pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Hash>>(
 	config: Configuration,
-	cli: &Cli,
-) -> Result<TaskManager, ServiceError> {
+	engine: Box<dyn ConsensusEngine>,
+) -> Result<(TaskManager, Option<AuthoringHandle>), ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -124,10 +167,10 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: mut telemetry,
-	} = new_partial(&config)?;
+		other: Other { mut telemetry, grandpa },
+	} = new_partial(&config, &*engine)?;
 
-	let net_config = sc_network::config::FullNetworkConfiguration::<
+	let mut net_config = sc_network::config::FullNetworkConfiguration::<
 		Block,
 		<Block as BlockT>::Hash,
 		Network,
@@ -139,6 +182,21 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 		config.prometheus_config.as_ref().map(|cfg| &cfg.registry),
 	);
 
+	let grandpa_protocol_name = sc_consensus_grandpa::protocol_standard_name(
+		&client.block_hash(0).ok().flatten().expect("Genesis block exists; qed"),
+		&config.chain_spec,
+	);
+	let grandpa_notification_service = grandpa.is_some().then(|| {
+		let (grandpa_protocol_config, grandpa_notification_service) =
+			sc_consensus_grandpa::grandpa_peers_set_config::<_, Network>(
+				grandpa_protocol_name.clone(),
+				metrics.clone(),
+				net_config.peer_store_handle(),
+			);
+		net_config.add_notification_protocol(grandpa_protocol_config);
+		grandpa_notification_service
+	});
+
 	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
 			config: &config,
@@ -161,12 +219,13 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 				runtime_api_provider: client.clone(),
 				is_validator: config.role.is_authority(),
 				keystore: Some(keystore_container.keystore()),
-				offchain_db: Some(backend.offchain_storage()
-					.expect("Offchain storage should be available")),
+				offchain_db: Some(
+					backend.offchain_storage().expect("Offchain storage should be available"),
+				),
 				transaction_pool: Some(OffchainTransactionPoolFactory::new(
 					transaction_pool.clone(),
 				)),
-					network_provider: Arc::new(network.clone()),
+				network_provider: Arc::new(network.clone()),
 				enable_http_requests: true,
 				custom_extensions: |_| vec![],
 			})
@@ -175,20 +234,49 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 		);
 	}
 
+	let shared_voter_state = sc_consensus_grandpa::SharedVoterState::empty();
+
+	let engine_rpc = engine.rpc_extensions(&config.role)?;
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
-
-		Box::new(move |_| {
-			let deps = crate::rpc::FullDeps { client: client.clone(), pool: pool.clone() };
+		let backend = backend.clone();
+		let shared_voter_state = shared_voter_state.clone();
+		let grandpa = grandpa
+			.as_ref()
+			.map(|(_, link)| (link.shared_authority_set().clone(), link.justification_stream()));
+
+		Box::new(move |subscription_executor| {
+			let grandpa = grandpa.clone().map(|(shared_authority_set, justification_stream)| {
+				crate::rpc::GrandpaDeps {
+					shared_voter_state: shared_voter_state.clone(),
+					finality_provider: sc_consensus_grandpa::FinalityProofProvider::new_for_service(
+						backend.clone(),
+						Some(shared_authority_set.clone()),
+					),
+					shared_authority_set,
+					justification_stream,
+					subscription_executor,
+				}
+			});
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				grandpa,
+				engine: engine_rpc.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
 
 	let prometheus_registry = config.prometheus_registry().cloned();
+	let keystore_path = config.keystore.path().map(|path| path.to_path_buf());
+	let dev_key_seed = config.dev_key_seed.clone();
+	let role = config.role;
+	let name = config.network.node_name.clone();
 
 	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
-		network,
+		network: network.clone(),
 		client: client.clone(),
 		keystore: keystore_container.keystore(),
 		task_manager: &mut task_manager,
@@ -197,7 +285,7 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 		backend,
 		system_rpc_tx,
 		tx_handler_controller,
-		sync_service,
+		sync_service: sync_service.clone(),
 		config,
 		telemetry: telemetry.as_mut(),
 	})?;
@@ -210,98 +298,62 @@ pub fn new_full<Network: sc_network::NetworkBackend<Block, <Block as BlockT>::Ha
 		telemetry.as_ref().map(|x| x.handle()),
 	);
 
-	let _consensus_result = match (cli.validator_id, cli.total_validators) {
-		(Some(validator_id), Some(total_validators)) => {
-			let round_robin: RoundRobinConsensus<Block, _, DatabaseBackend<Block>> = RoundRobinConsensus::new(
-				client.clone(),
-				Box::new(client.clone()) as Box<dyn BlockImport<Block, Error = ConsensusError> + Send>,
-				validator_id,
-				total_validators,
-			);
-
-			task_manager.spawn_essential_handle().spawn_blocking(
-				"round-robin-consensus",
-				None,
-				async move {
-					let mut round_robin = round_robin;
-					round_robin.run().await
-				},
-			);
-			Ok(())
-		}
-		(None, None) => {
-			info!("Node running in non-validator mode");
-			Ok(())
-		}
-		_ => {
-			Err(ServiceError::Other(
-				"Both --validator-id and --total-validators must be specified for validator mode".into(),
-			))
-		}
-	}?;
-
-	match cli.consensus {
-		Consensus::InstantSeal => {
-			let params = sc_consensus_manual_seal::InstantSealParams {
-				block_import: client.clone(),
-				env: proposer,
-				client,
-				pool: transaction_pool,
-				select_chain,
-				consensus_data_provider: None,
-				create_inherent_data_providers: move |_, ()| async move {
-					Ok(sp_timestamp::InherentDataProvider::from_system_time())
-				},
-			};
-
-			let authorship_future = sc_consensus_manual_seal::run_instant_seal(params);
-
-			task_manager.spawn_essential_handle().spawn_blocking(
-				"instant-seal",
-				None,
-				authorship_future,
-			);
-		},
-		Consensus::ManualSeal(block_time) => {
-			let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
-			task_manager.spawn_handle().spawn("block_authoring", None, async move {
-				loop {
-					futures_timer::Delay::new(std::time::Duration::from_millis(block_time)).await;
-					sink.try_send(sc_consensus_manual_seal::EngineCommand::SealNewBlock {
-						create_empty: true,
-						finalize: true,
-						parent_hash: None,
-						sender: None,
-					})
-					.unwrap();
-				}
-			});
-
-			let params = sc_consensus_manual_seal::ManualSealParams {
-				block_import: client.clone(),
-				env: proposer,
-				client,
-				pool: transaction_pool,
-				select_chain,
-				commands_stream: Box::pin(commands_stream),
-				consensus_data_provider: None,
-				create_inherent_data_providers: move |_, ()| async move {
-					Ok(sp_timestamp::InherentDataProvider::from_system_time())
-				},
-			};
-			let authorship_future = sc_consensus_manual_seal::run_manual_seal(params);
+	let authoring_block_import = block_import(&client, &grandpa);
+
+	if let (Some((_, grandpa_link)), Some(notification_service)) =
+		(grandpa, grandpa_notification_service)
+	{
+		let grandpa_config = sc_consensus_grandpa::Config {
+			gossip_duration: Duration::from_millis(333),
+			justification_generation_period: GRANDPA_JUSTIFICATION_PERIOD,
+			name: Some(name),
+			observer_enabled: false,
+			keystore: role.is_authority().then(|| keystore_container.keystore()),
+			local_role: role,
+			telemetry: telemetry.as_ref().map(|x| x.handle()),
+			protocol_name: grandpa_protocol_name,
+		};
+
+		// Votes to finalize blocks if our GRANDPA key is in the voter set, and follows the
+		// finality of the other voters otherwise
+		let grandpa_voter =
+			sc_consensus_grandpa::run_grandpa_voter(sc_consensus_grandpa::GrandpaParams {
+				config: grandpa_config,
+				link: grandpa_link,
+				network,
+				sync: Arc::new(sync_service.clone()),
+				notification_service,
+				voting_rule: sc_consensus_grandpa::VotingRulesBuilder::default().build(),
+				prometheus_registry: prometheus_registry.clone(),
+				shared_voter_state,
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
+				offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(
+					transaction_pool.clone(),
+				),
+			})?;
 
-			task_manager.spawn_essential_handle().spawn_blocking(
-				"manual-seal",
-				None,
-				authorship_future,
-			);
-		},
-		Consensus::RoundRobin { .. } => {
-			// Round robin is handled by the earlier match statement
-		},
+		task_manager
+			.spawn_essential_handle()
+			.spawn_blocking("grandpa-voter", None, grandpa_voter);
 	}
 
+	let authoring = engine.authoring_handle();
+	engine.start_authoring(AuthoringParams {
+		client,
+		select_chain,
+		block_import: authoring_block_import,
+		proposer,
+		transaction_pool,
+		keystore: keystore_container.keystore(),
+		keystore_path,
+		dev_key_seed,
+		sync_service,
+		role,
+		registry: prometheus_registry,
+		telemetry: telemetry.as_ref().map(|x| x.handle()),
+		task_manager: &task_manager,
+	})?;
+
 	network_starter.start_network();
-	Ok(task_manager)
+	Ok((task_manager, authoring))
 }
@@ -0,0 +1,132 @@
+//! Block production on inherent data from pluggable providers.
+
+use futures::{
+	executor,
+	future::{self, Ready},
+};
+use minimal_template_node::consensus::{BasicBlockProducer, BlockProducer};
+use minimal_template_runtime::interface::OpaqueBlock as Block;
+use polkadot_sdk::{
+	sc_client_api::{backend::NewBlockState, in_mem::Blockchain},
+	sp_consensus::{
+		DisableProofRecording, Environment, Error as ConsensusError, Proposal, Proposer,
+	},
+	sp_core::H256,
+	sp_inherents::InherentData,
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT},
+		Digest, DigestItem,
+	},
+	sp_timestamp,
+};
+use std::{sync::Arc, time::Duration};
+
+type Header = <Block as BlockT>::Header;
+
+/// Proposes empty blocks keeping the timestamp of their inherent data in the extrinsics root, for
+/// lack of a runtime to set it.
+struct TimestampEnv;
+
+struct TimestampProposer {
+	parent: Header,
+}
+
+impl Environment<Block> for TimestampEnv {
+	type Proposer = TimestampProposer;
+	type CreateProposer = Ready<Result<TimestampProposer, ConsensusError>>;
+	type Error = ConsensusError;
+
+	fn init(&mut self, parent_header: &Header) -> Self::CreateProposer {
+		future::ready(Ok(TimestampProposer { parent: parent_header.clone() }))
+	}
+}
+
+impl Proposer<Block> for TimestampProposer {
+	type Error = ConsensusError;
+	type Proposal = Ready<Result<Proposal<Block, ()>, ConsensusError>>;
+	type ProofRecording = DisableProofRecording;
+	type Proof = ();
+
+	fn propose(
+		self,
+		inherent_data: InherentData,
+		inherent_digests: Digest,
+		_max_duration: Duration,
+		_block_size_limit: Option<usize>,
+	) -> Self::Proposal {
+		let timestamp = inherent_data
+			.get_data::<sp_timestamp::InherentType>(&sp_timestamp::INHERENT_IDENTIFIER)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))
+			.and_then(|timestamp| {
+				timestamp.ok_or(ConsensusError::Other("No timestamp inherent data".into()))
+			});
+		future::ready(timestamp.map(|timestamp| {
+			let header = Header::new(
+				self.parent.number() + 1,
+				H256::from_low_u64_be(*timestamp),
+				Default::default(),
+				self.parent.hash(),
+				inherent_digests,
+			);
+			Proposal {
+				block: Block::new(header, Vec::new()),
+				proof: (),
+				storage_changes: Default::default(),
+			}
+		}))
+	}
+}
+
+/// A chain of only its genesis block.
+fn genesis_chain() -> (Arc<Blockchain<Block>>, Header) {
+	let chain = Blockchain::new();
+	let genesis = Header::new(
+		0,
+		Default::default(),
+		Default::default(),
+		Default::default(),
+		Default::default(),
+	);
+	chain
+		.insert(genesis.hash(), genesis.clone(), None, Some(Vec::new()), NewBlockState::Final)
+		.expect("Genesis inserts into an empty chain; qed");
+	(Arc::new(chain), genesis)
+}
+
+#[test]
+fn blocks_get_the_inherent_data_of_injected_providers() {
+	const TIMESTAMP: u64 = 1_700_000_000_000;
+	let (client, genesis) = genesis_chain();
+	let mut producer = BasicBlockProducer::new(
+		client,
+		TimestampEnv,
+		|_, digests: Digest| async move {
+			// Providers see the digests of the block, such as a pre-digest claiming a slot
+			let offset = digests.logs().len() as u64;
+			Ok(sp_timestamp::InherentDataProvider::new((TIMESTAMP + offset).into()))
+		},
+		Duration::from_secs(1),
+	);
+
+	let digests = Digest { logs: vec![DigestItem::Other(b"pre-digest".to_vec())] };
+	let block = executor::block_on(producer.produce_block(genesis.hash(), digests.clone()))
+		.expect("The proposer does not fail; qed");
+
+	assert_eq!(block.header().extrinsics_root, H256::from_low_u64_be(TIMESTAMP + 1));
+	assert_eq!(*block.header().parent_hash(), genesis.hash());
+	assert_eq!(block.header().digest(), &digests);
+}
+
+#[test]
+fn blocks_fail_without_the_inherent_data_the_proposer_needs() {
+	let (client, genesis) = genesis_chain();
+	let mut producer = BasicBlockProducer::new(
+		client,
+		TimestampEnv,
+		|_, _: Digest| async { Ok(()) },
+		Duration::from_secs(1),
+	);
+
+	let result = executor::block_on(producer.produce_block(genesis.hash(), Digest::default()));
+	assert!(result.is_err());
+}
@@ -0,0 +1,223 @@
+//! Round-robin fork choice between competing forks.
+
+mod sim;
+
+use minimal_template_node::consensus::{load_chain_weight, ChainWeight};
+use minimal_template_runtime::interface::OpaqueBlock as Block;
+use polkadot_sdk::{
+	sp_blockchain::HeaderBackend,
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT},
+		Digest, DigestItem,
+	},
+};
+use sim::{ChainConfig, Simulation};
+use sp_consensus_round_robin::{CompatibleDigestItem, PreDigest};
+use std::time::Duration;
+
+type Hash = <Block as BlockT>::Hash;
+type Header = <Block as BlockT>::Header;
+
+/// Round-robin authorities, as many as in [`Chain`]'s authority set.
+const AUTHORITIES: usize = 3;
+
+/// A simulated chain importing unsealed headers through the round-robin fork choice.
+struct Chain {
+	sim: Simulation,
+	genesis: Hash,
+}
+
+impl Chain {
+	/// A chain whose turns are given by slots.
+	fn with_slots() -> Self {
+		let sim = Simulation::new(ChainConfig {
+			slot_duration: Some(1_000),
+			..ChainConfig::new(AUTHORITIES)
+		});
+		let genesis = sim.client(0).info().genesis_hash;
+		Self { sim, genesis }
+	}
+
+	/// Imports a block on `parent` claiming `slot` in place of `skipped` authorities, and returns
+	/// its hash.
+	fn import(&self, parent: Hash, number: u32, slot: u64, skipped: u32) -> Hash {
+		let pre_digest = PreDigest { authority_index: 0, slot: slot.into(), skipped };
+		let digest = Digest { logs: vec![DigestItem::round_robin_pre_digest(pre_digest)] };
+		let header = Header::new(number, Default::default(), Default::default(), parent, digest);
+		let hash = header.hash();
+		self.sim.import_header(0, header);
+		hash
+	}
+
+	fn best_hash(&self) -> Hash {
+		self.sim.best_hash(0)
+	}
+
+	/// The chain weight recorded for `block` on import.
+	fn weight(&self, block: Hash) -> ChainWeight {
+		load_chain_weight::<Block, _>(self.sim.client(0), block).unwrap()
+	}
+}
+
+/// The weight of a fork of `authorities` authorities whose blocks skipped the given numbers of
+/// authorities and slots, on top of `base`.
+fn fork(base: ChainWeight, authorities: usize, blocks: &[(u32, u64)]) -> ChainWeight {
+	blocks.iter().fold(base, |weight, (skipped, skipped_slots)| {
+		weight.extend(authorities, *skipped, *skipped_slots)
+	})
+}
+
+/// The genesis header of the simulated chains.
+fn genesis(sim: &Simulation) -> Header {
+	sim.client(0).expect_header(sim.client(0).info().genesis_hash)
+}
+
+/// The chain weight recorded for `block` by `validator` on import.
+fn weight(sim: &Simulation, validator: usize, block: &Block) -> ChainWeight {
+	load_chain_weight::<Block, _>(sim.client(validator), block.hash())
+		.expect("The in-memory chain does not fail; qed")
+}
+
+#[test]
+fn scheduled_leader_beats_fallback_author_at_same_height() {
+	let base = fork(ChainWeight::default(), 3, &[(0, 0); 5]);
+	let leader = fork(base, 3, &[(0, 0)]);
+	let fallback = fork(base, 3, &[(1, 0)]);
+
+	assert!(leader > fallback);
+}
+
+#[test]
+fn fewer_skips_beat_more_skips_at_same_height() {
+	let base = ChainWeight::default();
+	let one_skip = fork(base, 4, &[(1, 0), (0, 0)]);
+	let two_skips = fork(base, 4, &[(2, 0), (0, 0)]);
+
+	assert!(one_skip > two_skips);
+}
+
+#[test]
+fn scheduled_blocks_beat_longer_chain_of_fallback_blocks() {
+	let base = fork(ChainWeight::default(), 4, &[(0, 0); 3]);
+	let scheduled = fork(base, 4, &[(0, 0)]);
+	let fallbacks = fork(base, 4, &[(3, 0), (3, 0), (3, 0)]);
+
+	assert!(scheduled > fallbacks);
+}
+
+#[test]
+fn longer_chain_with_a_fallback_block_beats_shorter_scheduled_chain() {
+	let base = ChainWeight::default();
+	let shorter = fork(base, 3, &[(0, 0), (0, 0)]);
+	let longer = fork(base, 3, &[(0, 0), (1, 0), (0, 0)]);
+
+	assert!(longer > shorter);
+}
+
+#[test]
+fn any_block_extends_the_best_chain() {
+	let best = fork(ChainWeight::default(), 3, &[(0, 0); 4]);
+
+	for skipped in 0..3 {
+		assert!(best.extend(3, skipped, 0) > best);
+		assert!(best.extend(3, skipped, 100) > best);
+	}
+	// Even past the authority set size, as after the set shrinks
+	assert!(best.extend(3, 5, 0) > best);
+}
+
+#[test]
+fn fewer_skipped_slots_break_ties() {
+	let base = fork(ChainWeight::default(), 3, &[(0, 0); 2]);
+	let dense = fork(base, 3, &[(0, 0), (0, 1)]);
+	let sparse = fork(base, 3, &[(0, 2), (0, 1)]);
+
+	assert!(dense > sparse);
+	assert_eq!(dense.authorship, sparse.authorship);
+}
+
+#[test]
+fn more_blocks_beat_fewer_skipped_slots() {
+	let base = ChainWeight::default();
+	let longer = fork(base, 3, &[(0, 5), (0, 5), (0, 5)]);
+	let shorter = fork(base, 3, &[(0, 0), (0, 0)]);
+
+	assert!(longer > shorter);
+}
+
+#[test]
+fn scheduled_block_replaces_fallback_block_of_same_height() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	let genesis = genesis(&sim);
+	sim.run_for(Duration::from_secs(3));
+
+	// Validator 1 misses its turn for block #1, which validator 2 takes
+	let fallback = sim.author_block(2, &genesis, 1);
+	sim.import(0, fallback.clone()).unwrap();
+	assert_eq!(sim.best_hash(0), fallback.hash());
+
+	let scheduled = sim.author_block(1, &genesis, 0);
+	sim.import(0, scheduled.clone()).unwrap();
+	assert_eq!(sim.best_hash(0), scheduled.hash());
+	assert_eq!(weight(&sim, 0, &fallback), ChainWeight { authorship: 2, skipped_slots: 0 });
+	assert_eq!(weight(&sim, 0, &scheduled), ChainWeight { authorship: 3, skipped_slots: 0 });
+}
+
+#[test]
+fn shorter_scheduled_chain_reorgs_longer_chain_of_fallback_blocks() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	let genesis = genesis(&sim);
+
+	// Validators 1 and 2 miss their turns for blocks #1 and #2
+	sim.run_for(Duration::from_secs(6));
+	let first = sim.author_block(0, &genesis, 2);
+	sim.run_for(Duration::from_secs(6));
+	let second = sim.author_block(1, first.header(), 2);
+	sim.import(0, first).unwrap();
+	sim.import(0, second.clone()).unwrap();
+	assert_eq!(sim.best_hash(0), second.hash());
+
+	let scheduled = sim.author_block(1, &genesis, 0);
+	sim.import(0, scheduled.clone()).unwrap();
+	assert_eq!(sim.best_hash(0), scheduled.hash());
+	assert!(weight(&sim, 0, &scheduled) > weight(&sim, 0, &second));
+}
+
+#[test]
+fn equal_forks_keep_the_first_imported_best() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	let genesis = genesis(&sim);
+
+	// Fork A has the scheduled block #1 and a fallback #2, fork B a fallback #1 and the
+	// scheduled #2
+	sim.run_for(Duration::from_secs(3));
+	let a1 = sim.author_block(1, &genesis, 0);
+	let b1 = sim.author_block(2, &genesis, 1);
+	sim.run_for(Duration::from_secs(3));
+	let a2 = sim.author_block(0, a1.header(), 1);
+	let b2 = sim.author_block(2, b1.header(), 0);
+
+	for block in [&a1, &a2, &b1, &b2] {
+		sim.import(0, block.clone()).unwrap();
+	}
+	for block in [&b1, &b2, &a1, &a2] {
+		sim.import(1, block.clone()).unwrap();
+	}
+
+	assert_eq!(weight(&sim, 0, &a2), weight(&sim, 0, &b2));
+	assert_eq!(sim.best_hash(0), a2.hash());
+	assert_eq!(sim.best_hash(1), b2.hash());
+}
+
+#[test]
+fn fewer_skipped_slots_win_on_import() {
+	let chain = Chain::with_slots();
+	let first = chain.import(chain.genesis, 1, 1, 0);
+
+	let sparse = chain.import(first, 2, 4, 0);
+	assert_eq!(chain.best_hash(), sparse);
+	let dense = chain.import(first, 2, 2, 0);
+	assert_eq!(chain.best_hash(), dense);
+	assert_eq!(chain.weight(dense), ChainWeight { authorship: 6, skipped_slots: 0 });
+	assert_eq!(chain.weight(sparse), ChainWeight { authorship: 6, skipped_slots: 2 });
+}
@@ -0,0 +1,982 @@
+//! A deterministic simulation of round-robin validators.
+//!
+//! Every validator runs the real [`RoundRobinConsensus`] task on its own in-memory chain. All
+//! tasks share one mocked [`Clock`] and run on a single-threaded executor, so time only moves when
+//! every task is idle. Blocks travel between validators through a simulated network that can
+//! delay them, drop them across a partition or withhold them from crashed validators, and are
+//! imported through the real [`RoundRobinVerifier`].
+//!
+//! Shared by several test crates, each using part of it.
+
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use codec::{Decode, Encode};
+use futures::{
+	channel::oneshot,
+	executor::{self, LocalPool},
+	future::{BoxFuture, RemoteHandle},
+	task::LocalSpawnExt,
+	FutureExt,
+};
+use minimal_template_node::consensus::{
+	AuthoringHandle, AuthorityKeystore, BlockProducer, Clock, PqKeystore, RoundRobinBlockImport,
+	RoundRobinConsensus, RoundRobinTiming, RoundRobinVerifier,
+};
+use minimal_template_runtime::interface::OpaqueBlock as Block;
+use polkadot_sdk::{
+	sc_client_api::{
+		backend::NewBlockState, in_mem::Blockchain, AuxStore, BlockImportNotification,
+		BlockchainEvents, FinalityNotifications, ImportNotifications, StorageEventStream,
+		UnpinWorkerMessage,
+	},
+	sc_consensus::{
+		BlockCheckParams, BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult,
+		Verifier,
+	},
+	sc_transaction_pool_api::{OffchainTransactionPoolFactory, RejectAllTxPool},
+	sc_utils::mpsc::{tracing_unbounded, TracingUnboundedSender},
+	sp_api::{ApiError, ApiRef, ProvideRuntimeApi},
+	sp_block_builder::BlockBuilder,
+	sp_blockchain::{self, BlockStatus, HeaderBackend, Info},
+	sp_consensus::{BlockOrigin, Error as ConsensusError},
+	sp_core::{hashing::blake2_256, storage::StorageKey, H256},
+	sp_inherents::{CheckInherentsResult, CreateInherentDataProviders, InherentData},
+	sp_keystore::{testing::MemoryKeystore, Keystore},
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT, NumberFor},
+		Digest, DigestItem, OpaqueExtrinsic,
+	},
+	sp_timestamp,
+};
+use sp_consensus_round_robin::{
+	find_pre_digest, shuffle_schedule, weighted_schedule, AuthorityId, AuthorityIndex,
+	CompatibleDigestItem, EquivocationProof, PreDigest, RoundRobinApi, Slot, SlotDuration,
+	KEY_TYPE,
+};
+use std::{
+	collections::BTreeMap,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+pub type Header = <Block as BlockT>::Header;
+
+/// When the simulated chains start, since the Unix epoch.
+pub const GENESIS_TIME: Duration = Duration::from_secs(1_700_000_000);
+
+/// How far ahead of the inherent data a block's timestamp may be, as in `pallet_timestamp`.
+const MAX_TIMESTAMP_DRIFT_MILLIS: u64 = 30_000;
+
+/// The timestamp of a simulated block, kept in its extrinsics root for lack of a runtime.
+pub fn timestamp_of(header: &Header) -> u64 {
+	header.extrinsics_root.to_low_u64_be()
+}
+
+/// The only extrinsic of a simulated block, standing in for the timestamp inherent.
+fn timestamp_extrinsic(timestamp: u64) -> OpaqueExtrinsic {
+	OpaqueExtrinsic::from_bytes(&timestamp.encode().encode())
+		.expect("An encoded byte vector decodes as an opaque extrinsic; qed")
+}
+
+/// The timestamp set by the timestamp extrinsic among `extrinsics`, if any.
+fn timestamp_in(extrinsics: &[OpaqueExtrinsic]) -> Option<u64> {
+	let bytes = Vec::<u8>::decode(&mut &extrinsics.first()?.encode()[..]).ok()?;
+	u64::decode(&mut &bytes[..]).ok()
+}
+
+/// The chain all validators agree on at genesis.
+#[derive(Clone, Debug)]
+pub struct ChainConfig {
+	/// The number of validators, all of them authorities.
+	pub validators: usize,
+	/// The minimum time between the arrival of a block and building on it.
+	pub block_time: Duration,
+	/// How long, in milliseconds, until the next authority may author in place of one that
+	/// missed its turn, or `None` to wait for the scheduled authority forever.
+	pub missed_turn_timeout: Option<u64>,
+	/// The slot duration in milliseconds, or `None` to give turns by height.
+	pub slot_duration: Option<u64>,
+	/// The weight of the turns of each validator.
+	pub weights: Vec<u64>,
+	/// The number of slots the schedule is shuffled for at a time, or `None` to keep it in
+	/// order.
+	pub epoch_length: Option<u64>,
+}
+
+impl ChainConfig {
+	/// Turns by height in index order among `validators` of equal weight, with a 1s block time and
+	/// a 3s missed-turn timeout.
+	pub fn new(validators: usize) -> Self {
+		Self {
+			validators,
+			block_time: Duration::from_secs(1),
+			missed_turn_timeout: Some(3_000),
+			slot_duration: None,
+			weights: vec![1; validators],
+			epoch_length: None,
+		}
+	}
+
+	/// The schedule of `slot`: the turns weighted as the pallet weighs them, shuffled with a
+	/// seed of its epoch if epochs are set.
+	pub fn schedule(&self, slot: Slot) -> Vec<AuthorityIndex> {
+		let mut schedule = weighted_schedule(&self.weights, 1024);
+		if let Some(epoch_length) = self.epoch_length {
+			shuffle_schedule(&mut schedule, &blake2_256(&(*slot / epoch_length).encode()));
+		}
+		schedule
+	}
+
+	/// The minimum period between the timestamps of a block and its parent, in milliseconds, as
+	/// the runtime derives it: half the slot duration or, with turns by height, the block time.
+	pub fn minimum_period(&self) -> u64 {
+		let period = self.slot_duration.unwrap_or(self.block_time.as_millis() as u64);
+		(period / 2).max(1)
+	}
+}
+
+/// A clock that only moves when the simulation advances it.
+pub struct SimClock {
+	state: Mutex<ClockState>,
+}
+
+struct ClockState {
+	now: Duration,
+	sleepers: Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+impl SimClock {
+	fn new(now: Duration) -> Self {
+		Self { state: Mutex::new(ClockState { now, sleepers: Vec::new() }) }
+	}
+
+	/// When the next sleeper wakes up.
+	fn next_wake(&self) -> Option<Duration> {
+		let mut state = self.state.lock().unwrap();
+		state.sleepers.retain(|(_, waker)| !waker.is_canceled());
+		state.sleepers.iter().map(|(at, _)| *at).min()
+	}
+
+	/// Moves the clock forward to `to`, waking every sleeper due by then.
+	fn advance_to(&self, to: Duration) {
+		let mut state = self.state.lock().unwrap();
+		state.now = state.now.max(to);
+		let now = state.now;
+		let (due, sleeping) = std::mem::take(&mut state.sleepers)
+			.into_iter()
+			.partition::<Vec<_>, _>(|(at, _)| *at <= now);
+		state.sleepers = sleeping;
+		for (_, waker) in due {
+			let _ = waker.send(());
+		}
+	}
+}
+
+impl Clock for SimClock {
+	fn now(&self) -> Duration {
+		self.state.lock().unwrap().now
+	}
+
+	fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+		let (waker, sleeper) = oneshot::channel();
+		let mut state = self.state.lock().unwrap();
+		let at = state.now + duration;
+		state.sleepers.push((at, waker));
+		async move {
+			let _ = sleeper.await;
+		}
+		.boxed()
+	}
+}
+
+/// The in-memory chain of one validator.
+pub struct SimClient {
+	chain: Blockchain<Block>,
+	config: ChainConfig,
+	authorities: Vec<AuthorityId>,
+	import_listeners: Mutex<Vec<TracingUnboundedSender<BlockImportNotification<Block>>>>,
+	unpin_worker: TracingUnboundedSender<UnpinWorkerMessage<Block>>,
+	/// Blocks this validator authored that were not gossiped yet.
+	authored: Mutex<Vec<Block>>,
+}
+
+impl SimClient {
+	fn new(config: ChainConfig, authorities: Vec<AuthorityId>) -> Self {
+		let chain = Blockchain::new();
+		let genesis = Header::new(
+			0,
+			H256::from_low_u64_be(GENESIS_TIME.as_millis() as u64),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		chain
+			.insert(genesis.hash(), genesis, None, Some(Vec::new()), NewBlockState::Final)
+			.expect("Genesis inserts into an empty chain; qed");
+
+		// Nobody unpins blocks of an in-memory chain
+		let (unpin_worker, _) = tracing_unbounded("sim-unpin-worker", 100_000);
+		Self {
+			chain,
+			config,
+			authorities,
+			import_listeners: Mutex::new(Vec::new()),
+			unpin_worker,
+			authored: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// Imports `block`, notifying authoring of it.
+	fn import(&self, block: BlockImportParams<Block>) -> Result<ImportResult, ConsensusError> {
+		let header = block.post_header();
+		let hash = header.hash();
+		let error = |e: sp_blockchain::Error| ConsensusError::ClientImport(e.to_string());
+		if self.chain.header(hash).map_err(error)?.is_some() {
+			return Ok(ImportResult::AlreadyInChain);
+		}
+		if self.chain.header(*header.parent_hash()).map_err(error)?.is_none() {
+			return Ok(ImportResult::UnknownParent);
+		}
+
+		let inserted: Vec<(&[u8], &[u8])> = block
+			.auxiliary
+			.iter()
+			.filter_map(|(key, value)| value.as_ref().map(|value| (&key[..], &value[..])))
+			.collect();
+		let deleted: Vec<&[u8]> = block
+			.auxiliary
+			.iter()
+			.filter(|(_, value)| value.is_none())
+			.map(|(key, _)| &key[..])
+			.collect();
+		self.chain.insert_aux(&inserted, &deleted).map_err(error)?;
+
+		let is_new_best = match block.fork_choice {
+			Some(ForkChoiceStrategy::Custom(is_new_best)) => is_new_best,
+			Some(ForkChoiceStrategy::LongestChain) =>
+				*header.number() > self.chain.info().best_number,
+			None => false,
+		};
+		let state = if is_new_best { NewBlockState::Best } else { NewBlockState::Normal };
+		let body = block.body.unwrap_or_default();
+		self.chain
+			.insert(hash, header.clone(), None, Some(body.clone()), state)
+			.map_err(error)?;
+
+		if block.origin == BlockOrigin::Own {
+			self.authored.lock().unwrap().push(Block::new(header.clone(), body));
+		}
+		self.import_listeners.lock().unwrap().retain(|listener| {
+			let notification = BlockImportNotification::new(
+				hash,
+				block.origin,
+				header.clone(),
+				is_new_best,
+				None,
+				self.unpin_worker.clone(),
+			);
+			listener.unbounded_send(notification).is_ok()
+		});
+
+		Ok(ImportResult::imported(is_new_best))
+	}
+
+	/// The header of the block `hash`, which must be known.
+	pub fn expect_header(&self, hash: H256) -> Header {
+		self.chain
+			.header(hash)
+			.expect("The in-memory chain does not fail; qed")
+			.unwrap_or_else(|| panic!("Unknown block {:?}", hash))
+	}
+
+	/// The headers of the best chain, from block #1 to the best block.
+	pub fn best_chain(&self) -> Vec<Header> {
+		let mut chain = Vec::new();
+		let mut header = self.expect_header(self.chain.info().best_hash);
+		while *header.number() > 0 {
+			let parent_hash = *header.parent_hash();
+			chain.push(header);
+			header = self.expect_header(parent_hash);
+		}
+		chain.reverse();
+		chain
+	}
+
+	/// The number of chain heads, one more than the number of forks.
+	pub fn leaves(&self) -> usize {
+		use sp_blockchain::Backend;
+		self.chain.leaves().expect("The in-memory chain does not fail; qed").len()
+	}
+}
+
+impl HeaderBackend<Block> for SimClient {
+	fn header(&self, hash: H256) -> sp_blockchain::Result<Option<Header>> {
+		self.chain.header(hash)
+	}
+
+	fn info(&self) -> Info<Block> {
+		self.chain.info()
+	}
+
+	fn status(&self, hash: H256) -> sp_blockchain::Result<BlockStatus> {
+		self.chain.status(hash)
+	}
+
+	fn number(&self, hash: H256) -> sp_blockchain::Result<Option<NumberFor<Block>>> {
+		self.chain.number(hash)
+	}
+
+	fn hash(&self, number: NumberFor<Block>) -> sp_blockchain::Result<Option<H256>> {
+		self.chain.hash(number)
+	}
+}
+
+impl AuxStore for SimClient {
+	fn insert_aux<
+		'a,
+		'b: 'a,
+		'c: 'a,
+		I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+		D: IntoIterator<Item = &'a &'b [u8]>,
+	>(
+		&self,
+		insert: I,
+		delete: D,
+	) -> sp_blockchain::Result<()> {
+		self.chain.insert_aux(insert, delete)
+	}
+
+	fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+		self.chain.get_aux(key)
+	}
+}
+
+impl BlockchainEvents<Block> for SimClient {
+	fn import_notification_stream(&self) -> ImportNotifications<Block> {
+		let (sender, receiver) = tracing_unbounded("sim-import-notifications", 100_000);
+		self.import_listeners.lock().unwrap().push(sender);
+		receiver
+	}
+
+	fn every_import_notification_stream(&self) -> ImportNotifications<Block> {
+		self.import_notification_stream()
+	}
+
+	fn finality_notification_stream(&self) -> FinalityNotifications<Block> {
+		// Nothing finalizes blocks in the simulation
+		tracing_unbounded("sim-finality-notifications", 100_000).1
+	}
+
+	fn storage_changes_notification_stream(
+		&self,
+		_filter_keys: Option<&[StorageKey]>,
+		_child_filter_keys: Option<&[(StorageKey, Option<Vec<StorageKey>>)]>,
+	) -> sp_blockchain::Result<StorageEventStream<H256>> {
+		Err(sp_blockchain::Error::Backend("The simulation has no storage".into()))
+	}
+}
+
+impl ProvideRuntimeApi<Block> for SimClient {
+	type Api = SimRuntimeApi;
+
+	fn runtime_api(&self) -> ApiRef<'_, Self::Api> {
+		SimRuntimeApi {
+			chain: self.chain.clone(),
+			config: self.config.clone(),
+			authorities: self.authorities.clone(),
+		}
+		.into()
+	}
+}
+
+/// The round-robin runtime API of a fixed authority set, scheduled by [`ChainConfig::schedule`],
+/// along with the inherent checks of the timestamp pallet.
+#[derive(Clone)]
+pub struct SimRuntimeApi {
+	chain: Blockchain<Block>,
+	config: ChainConfig,
+	authorities: Vec<AuthorityId>,
+}
+
+polkadot_sdk::sp_api::mock_impl_runtime_apis! {
+	impl RoundRobinApi<Block> for SimRuntimeApi {
+		fn authorities(&self) -> Vec<AuthorityId> {
+			self.authorities.clone()
+		}
+
+		fn slot_duration(&self) -> Option<SlotDuration> {
+			self.config.slot_duration.map(SlotDuration::from_millis)
+		}
+
+		fn schedule(&self, slot: Slot) -> Vec<AuthorityIndex> {
+			self.config.schedule(slot)
+		}
+
+		#[advanced]
+		fn fallback_timestamp(
+			&self,
+			at: <Block as BlockT>::Hash,
+			skipped: u32,
+		) -> Result<Option<u64>, ApiError> {
+			if self.config.slot_duration.is_some() {
+				return Ok(None);
+			}
+			let parent = self
+				.chain
+				.header(at)
+				.map_err(|e| ApiError::Application(Box::new(e)))?
+				.ok_or_else(|| ApiError::UnknownBlock(format!("{:?}", at)))?;
+			Ok(self
+				.config
+				.missed_turn_timeout
+				.map(|timeout| timestamp_of(&parent) + timeout * skipped as u64))
+		}
+
+		fn block_time(&self) -> Option<u64> {
+			self.config.slot_duration.is_none().then(|| self.config.block_time.as_millis() as u64)
+		}
+
+		fn missed_turn_timeout(&self) -> Option<u64> {
+			self.config.missed_turn_timeout
+		}
+
+		fn block_timestamp(
+			&self,
+			extrinsics: &Vec<<Block as BlockT>::Extrinsic>,
+		) -> Option<u64> {
+			timestamp_in(extrinsics)
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			&self,
+			_equivocation_proof: EquivocationProof<Header>,
+		) -> Option<()> {
+			None
+		}
+	}
+
+	impl BlockBuilder<Block> for SimRuntimeApi {
+		#[advanced]
+		fn check_inherents(
+			&self,
+			at: <Block as BlockT>::Hash,
+			block: Block,
+			data: InherentData,
+		) -> Result<CheckInherentsResult, ApiError> {
+			let parent = self
+				.chain
+				.header(at)
+				.map_err(|e| ApiError::Application(Box::new(e)))?
+				.ok_or_else(|| ApiError::UnknownBlock(format!("{:?}", at)))?;
+			let mut result = CheckInherentsResult::new();
+			let timestamp = timestamp_in(block.extrinsics());
+			let now =
+				data.get_data::<sp_timestamp::InherentType>(&sp_timestamp::INHERENT_IDENTIFIER);
+			let error = match (timestamp, now) {
+				(Some(timestamp), Ok(Some(now)))
+					if timestamp > now.as_millis() + MAX_TIMESTAMP_DRIFT_MILLIS =>
+				{
+					sp_timestamp::InherentError::TooFarInFuture
+				}
+				(Some(timestamp), _)
+					if timestamp < timestamp_of(&parent) + self.config.minimum_period() =>
+				{
+					sp_timestamp::InherentError::TooEarly
+				}
+				_ => return Ok(result),
+			};
+			result
+				.put_error(sp_timestamp::INHERENT_IDENTIFIER, &error)
+				.expect("The result holds no fatal error yet; qed");
+			Ok(result)
+		}
+	}
+}
+
+/// Provides the simulated time as the timestamp inherent data.
+#[derive(Clone)]
+struct SimInherentDataProviders(Arc<SimClock>);
+
+#[async_trait]
+impl CreateInherentDataProviders<Block, ()> for SimInherentDataProviders {
+	type InherentDataProviders = sp_timestamp::InherentDataProvider;
+
+	async fn create_inherent_data_providers(
+		&self,
+		_parent: H256,
+		_extra_args: (),
+	) -> Result<Self::InherentDataProviders, Box<dyn std::error::Error + Send + Sync>> {
+		Ok(sp_timestamp::InherentDataProvider::new(self.0.timestamp()))
+	}
+}
+
+type SimVerifier = RoundRobinVerifier<Block, SimClient, SimInherentDataProviders>;
+
+/// Imports blocks into a validator's chain, as the client does.
+struct SimBlockImport(Arc<SimClient>);
+
+#[async_trait]
+impl BlockImport<Block> for SimBlockImport {
+	type Error = ConsensusError;
+
+	async fn check_block(
+		&self,
+		_block: BlockCheckParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		Ok(ImportResult::imported(false))
+	}
+
+	async fn import_block(
+		&self,
+		block: BlockImportParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		self.0.import(block)
+	}
+}
+
+/// Imports blocks into a validator's chain through the round-robin fork choice.
+type SimRoundRobinImport = RoundRobinBlockImport<Block, SimBlockImport, SimClient>;
+
+/// Builds empty blocks stamped with the simulated time.
+struct SimProducer {
+	client: Arc<SimClient>,
+	clock: Arc<SimClock>,
+	/// How many of the next blocks to fail building.
+	failures: Arc<Mutex<u32>>,
+}
+
+#[async_trait]
+impl BlockProducer<Block> for SimProducer {
+	async fn produce_block(
+		&mut self,
+		parent_hash: H256,
+		inherent_digests: Digest,
+	) -> Result<Block, ConsensusError> {
+		{
+			let mut failures = self.failures.lock().unwrap();
+			if *failures > 0 {
+				*failures -= 1;
+				return Err(ConsensusError::ClientImport("Simulated build failure".into()));
+			}
+		}
+		let parent_number = self
+			.client
+			.number(parent_hash)
+			.map_err(|e| ConsensusError::Other(Box::new(e)))?
+			.ok_or(ConsensusError::ChainLookup("Parent header not found".into()))?;
+		let timestamp = self.clock.timestamp().as_millis();
+		let header = Header::new(
+			parent_number + 1,
+			H256::from_low_u64_be(timestamp),
+			Default::default(),
+			parent_hash,
+			inherent_digests,
+		);
+		Ok(Block::new(header, vec![timestamp_extrinsic(timestamp)]))
+	}
+}
+
+struct SimValidator {
+	client: Arc<SimClient>,
+	verifier: SimVerifier,
+	/// Shared by the block imports of authoring and of blocks from the network.
+	import_lock: Arc<futures::lock::Mutex<()>>,
+	keystore: AuthorityKeystore,
+	/// The authoring task, dropped to crash the validator.
+	authoring: Option<RemoteHandle<()>>,
+	handle: AuthoringHandle,
+	/// How many of the next blocks to fail building.
+	failures: Arc<Mutex<u32>>,
+	/// Blocks that arrived before their parent.
+	orphans: Vec<Block>,
+}
+
+/// A block on its way from `from` to `to`.
+struct InFlight {
+	at: Duration,
+	from: usize,
+	to: usize,
+	block: Block,
+}
+
+/// Validators authoring on their own chains and gossiping their blocks to each other.
+pub struct Simulation {
+	config: ChainConfig,
+	clock: Arc<SimClock>,
+	pool: LocalPool,
+	validators: Vec<SimValidator>,
+	in_flight: Vec<InFlight>,
+	/// Every block authored so far, with its author, for validators catching up.
+	history: Vec<(usize, Block)>,
+	/// The side of the partition of each validator, if the network is partitioned.
+	partition: Option<Vec<usize>>,
+	/// How long the blocks of each validator take to reach the others.
+	delays: BTreeMap<usize, Duration>,
+	/// The blocks each validator's verifier rejected, with the reason.
+	rejected: Vec<(usize, H256, String)>,
+}
+
+impl Simulation {
+	/// A network of validators with keys `//Validator0`, `//Validator1`, ..., none authoring yet.
+	pub fn new(config: ChainConfig) -> Self {
+		let keystores: Vec<MemoryKeystore> =
+			(0..config.validators).map(|_| MemoryKeystore::new()).collect();
+		let authorities: Vec<AuthorityId> = keystores
+			.iter()
+			.enumerate()
+			.map(|(index, keystore)| {
+				let seed = format!("//Validator{}", index);
+				let public = keystore
+					.sr25519_generate_new(KEY_TYPE, Some(&seed))
+					.expect("The in-memory keystore generates keys; qed");
+				AuthorityId::Sr25519(public.into())
+			})
+			.collect();
+
+		let clock = Arc::new(SimClock::new(GENESIS_TIME));
+		let validators = keystores
+			.into_iter()
+			.map(|keystore| {
+				let client = Arc::new(SimClient::new(config.clone(), authorities.clone()));
+				let verifier = RoundRobinVerifier::new(
+					client.clone(),
+					OffchainTransactionPoolFactory::new(RejectAllTxPool::default()),
+					SimInherentDataProviders(clock.clone()),
+				)
+				.with_clock(clock.clone());
+				SimValidator {
+					client,
+					verifier,
+					import_lock: Arc::default(),
+					keystore: AuthorityKeystore::new(
+						Arc::new(keystore),
+						Arc::new(PqKeystore::open(None).expect("In-memory keystores open; qed")),
+					),
+					authoring: None,
+					handle: AuthoringHandle::default(),
+					failures: Arc::default(),
+					orphans: Vec::new(),
+				}
+			})
+			.collect();
+
+		Self {
+			clock,
+			pool: LocalPool::new(),
+			validators,
+			in_flight: Vec::new(),
+			history: Vec::new(),
+			partition: None,
+			delays: BTreeMap::new(),
+			rejected: Vec::new(),
+			config,
+		}
+	}
+
+	/// Starts authoring on every validator.
+	pub fn start_all(&mut self) {
+		for validator in 0..self.validators.len() {
+			self.start(validator);
+		}
+	}
+
+	/// Starts authoring on `validator`.
+	pub fn start(&mut self, validator: usize) {
+		let client = self.validators[validator].client.clone();
+		let handle = AuthoringHandle::default();
+		let mut authoring = RoundRobinConsensus::new(
+			client.clone(),
+			Box::new(self.block_import(validator)),
+			SimProducer {
+				client,
+				clock: self.clock.clone(),
+				failures: self.validators[validator].failures.clone(),
+			},
+			self.validators[validator].keystore.clone(),
+			RoundRobinTiming::new(self.config.block_time),
+		)
+		.with_clock(self.clock.clone())
+		.with_handle(handle.clone());
+		let task = self
+			.pool
+			.spawner()
+			.spawn_local_with_handle(async move { authoring.run().await })
+			.expect("The local pool is running; qed");
+		self.validators[validator].authoring = Some(task);
+		self.validators[validator].handle = handle;
+	}
+
+	/// Imports blocks into the chain of `validator` through the round-robin fork choice.
+	fn block_import(&self, validator: usize) -> SimRoundRobinImport {
+		let validator = &self.validators[validator];
+		RoundRobinBlockImport::new(
+			SimBlockImport(validator.client.clone()),
+			validator.client.clone(),
+		)
+		.with_import_lock(validator.import_lock.clone())
+	}
+
+	/// The handle of the authoring task of `validator`, as of its last start.
+	pub fn authoring(&self, validator: usize) -> &AuthoringHandle {
+		&self.validators[validator].handle
+	}
+
+	/// Stops `validator` abruptly. It neither authors nor receives blocks until restarted, but
+	/// keeps its chain.
+	pub fn crash(&mut self, validator: usize) {
+		self.validators[validator].authoring = None;
+		self.validators[validator].orphans.clear();
+		self.in_flight.retain(|block| block.to != validator);
+	}
+
+	/// Makes the next `count` blocks `validator` builds fail, as a proposer or keystore error
+	/// would.
+	pub fn fail_builds(&mut self, validator: usize, count: u32) {
+		*self.validators[validator].failures.lock().unwrap() += count;
+	}
+
+	/// Restarts `validator`, which first catches up with the validators it can reach.
+	pub fn restart(&mut self, validator: usize) {
+		self.catch_up(validator);
+		self.start(validator);
+	}
+
+	/// Whether `validator` is running.
+	pub fn is_running(&self, validator: usize) -> bool {
+		self.validators[validator].authoring.is_some()
+	}
+
+	/// Splits the network into `sides`. Blocks only reach validators on the same side, and blocks
+	/// in flight across sides are lost.
+	pub fn partition(&mut self, sides: &[&[usize]]) {
+		let mut side_of = vec![usize::MAX; self.validators.len()];
+		for (side, validators) in sides.iter().enumerate() {
+			for validator in *validators {
+				side_of[*validator] = side;
+			}
+		}
+		self.in_flight.retain(|block| side_of[block.from] == side_of[block.to]);
+		self.partition = Some(side_of);
+	}
+
+	/// Joins the sides of a partition, letting every running validator catch up.
+	pub fn heal(&mut self) {
+		self.partition = None;
+		for validator in 0..self.validators.len() {
+			if self.is_running(validator) {
+				self.catch_up(validator);
+			}
+		}
+	}
+
+	/// Delays the blocks `validator` authors by `delay` on their way to the others.
+	pub fn delay(&mut self, validator: usize, delay: Duration) {
+		self.delays.insert(validator, delay);
+	}
+
+	/// The chain of `validator`.
+	pub fn client(&self, validator: usize) -> &SimClient {
+		&self.validators[validator].client
+	}
+
+	/// The simulated time elapsed since genesis.
+	pub fn elapsed(&self) -> Duration {
+		self.clock.now() - GENESIS_TIME
+	}
+
+	/// Runs the simulation for `duration` of simulated time.
+	pub fn run_for(&mut self, duration: Duration) {
+		let end = self.clock.now() + duration;
+		loop {
+			self.pool.run_until_stalled();
+			if self.gossip() || self.deliver_due() {
+				continue;
+			}
+
+			let next_delivery = self.in_flight.iter().map(|block| block.at).min();
+			let next = match (self.clock.next_wake(), next_delivery) {
+				(Some(wake), Some(delivery)) => Some(wake.min(delivery)),
+				(wake, delivery) => wake.or(delivery),
+			};
+			match next {
+				Some(next) if next <= end => self.clock.advance_to(next),
+				_ if self.clock.now() < end => self.clock.advance_to(end),
+				_ => break,
+			}
+		}
+	}
+
+	/// The best block number of `validator`.
+	pub fn best_number(&self, validator: usize) -> u32 {
+		self.client(validator).info().best_number
+	}
+
+	/// The best block hash of `validator`.
+	pub fn best_hash(&self, validator: usize) -> H256 {
+		self.client(validator).info().best_hash
+	}
+
+	/// The pre-digests of the best chain of `validator`, from block #1 on.
+	pub fn best_chain(&self, validator: usize) -> Vec<PreDigest> {
+		self.client(validator)
+			.best_chain()
+			.iter()
+			.map(|header| {
+				find_pre_digest(header).expect("Simulated blocks carry a pre-digest; qed")
+			})
+			.collect()
+	}
+
+	/// The blocks rejected on import so far: the validator rejecting each, its hash and why.
+	pub fn rejected(&self) -> &[(usize, H256, String)] {
+		&self.rejected
+	}
+
+	/// Builds a block on `parent` claiming to be by `author` after skipping `skipped` authorities,
+	/// stamped with the simulated time and sealed with the key of `author`. Nothing imports it.
+	pub fn author_block(&self, author: usize, parent: &Header, skipped: u32) -> Block {
+		let number = parent.number() + 1;
+		let timestamp = self.clock.timestamp();
+		let slot = match self.config.slot_duration {
+			Some(slot_duration) =>
+				Slot::from_timestamp(timestamp, SlotDuration::from_millis(slot_duration)),
+			None => Slot::from(number as u64),
+		};
+		let pre_digest = PreDigest { authority_index: author as AuthorityIndex, slot, skipped };
+		let mut header = Header::new(
+			number,
+			H256::from_low_u64_be(timestamp.as_millis()),
+			Default::default(),
+			parent.hash(),
+			Digest { logs: vec![DigestItem::round_robin_pre_digest(pre_digest)] },
+		);
+
+		let validator = &self.validators[author];
+		let signature = validator
+			.keystore
+			.sign(&validator.client.authorities[author], header.hash().as_ref())
+			.expect("Validators hold their own keys; qed");
+		header.digest_mut().push(DigestItem::round_robin_seal(signature));
+		Block::new(header, vec![timestamp_extrinsic(timestamp.as_millis())])
+	}
+
+	/// Imports `block` into the chain of `validator` as received from the network, through the
+	/// validator's [`RoundRobinVerifier`]. Returns why the verifier rejected it, if it did.
+	pub fn import(&self, validator: usize, block: Block) -> Result<ImportResult, String> {
+		let block_import = self.block_import(validator);
+		let validator = &self.validators[validator];
+		let (header, body) = block.deconstruct();
+		let known = |hash| {
+			let header = validator.client.header(hash);
+			header.expect("The in-memory chain does not fail; qed").is_some()
+		};
+		if known(header.hash()) {
+			return Ok(ImportResult::AlreadyInChain);
+		}
+		if !known(*header.parent_hash()) {
+			return Ok(ImportResult::UnknownParent);
+		}
+
+		let mut params = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+		params.body = Some(body);
+		let params = executor::block_on(validator.verifier.verify(params))?;
+		executor::block_on(block_import.import_block(params)).map_err(|e| e.to_string())
+	}
+
+	/// Imports `header` into the chain of `validator` as if it had passed the verifier, through
+	/// the round-robin fork choice alone. The header needs a pre-digest but no seal.
+	pub fn import_header(&self, validator: usize, header: Header) -> ImportResult {
+		let params = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+		executor::block_on(self.block_import(validator).import_block(params))
+			.expect("The in-memory chain does not fail; qed")
+	}
+
+	/// Asserts that `validators` agree on the best block.
+	pub fn assert_converged(&self, validators: &[usize]) {
+		for validator in validators {
+			assert_eq!(
+				self.best_hash(*validator),
+				self.best_hash(validators[0]),
+				"Validators {} and {} disagree on the best block after {:?}",
+				validators[0],
+				validator,
+				self.elapsed(),
+			);
+		}
+	}
+
+	fn reachable(&self, from: usize, to: usize) -> bool {
+		self.partition.as_ref().is_none_or(|side_of| side_of[from] == side_of[to])
+	}
+
+	/// Sends the blocks validators authored since the last call on their way to the others.
+	/// Returns whether there were any.
+	fn gossip(&mut self) -> bool {
+		let mut sent = false;
+		for from in 0..self.validators.len() {
+			let authored =
+				std::mem::take(&mut *self.validators[from].client.authored.lock().unwrap());
+			let at = self.clock.now() + self.delays.get(&from).copied().unwrap_or_default();
+			for block in authored {
+				for to in 0..self.validators.len() {
+					if to != from && self.is_running(to) && self.reachable(from, to) {
+						self.in_flight.push(InFlight { at, from, to, block: block.clone() });
+					}
+				}
+				self.history.push((from, block));
+				sent = true;
+			}
+		}
+		sent
+	}
+
+	/// Delivers the blocks due by now. Returns whether there were any.
+	fn deliver_due(&mut self) -> bool {
+		let now = self.clock.now();
+		let (due, in_flight) = std::mem::take(&mut self.in_flight)
+			.into_iter()
+			.partition::<Vec<_>, _>(|block| block.at <= now);
+		self.in_flight = in_flight;
+		let delivered = !due.is_empty();
+		for block in due {
+			self.deliver(block.to, block.block);
+		}
+		delivered
+	}
+
+	/// Hands `validator` every block authored by validators it can reach.
+	fn catch_up(&mut self, validator: usize) {
+		let blocks: Vec<Block> = self
+			.history
+			.iter()
+			.filter(|(from, _)| *from != validator && self.reachable(*from, validator))
+			.map(|(_, block)| block.clone())
+			.collect();
+		for block in blocks {
+			self.deliver(validator, block);
+		}
+	}
+
+	/// Imports `block` as received from the network, or keeps it until its parent arrives.
+	fn deliver(&mut self, validator: usize, block: Block) {
+		let mut pending = vec![block];
+		while let Some(block) = pending.pop() {
+			match self.import(validator, block.clone()) {
+				Ok(ImportResult::UnknownParent) => self.validators[validator].orphans.push(block),
+				Ok(ImportResult::Imported(_)) => {
+					// Any orphans of the block can follow now
+					let hash = block.header().hash();
+					let orphans = &mut self.validators[validator].orphans;
+					let (children, rest) = std::mem::take(orphans)
+						.into_iter()
+						.partition::<Vec<_>, _>(|orphan| *orphan.header().parent_hash() == hash);
+					*orphans = rest;
+					pending.extend(children);
+				},
+				Ok(_) => {},
+				Err(e) => self.rejected.push((validator, block.header().hash(), e)),
+			}
+		}
+	}
+}
@@ -0,0 +1,259 @@
+//! Round-robin validators in scripted network conditions, on simulated time.
+
+mod sim;
+
+use sim::{ChainConfig, Simulation};
+use sp_consensus_round_robin::scheduled_author;
+use std::time::Duration;
+
+/// The authority scheduled to author block `number` among `validators`.
+fn leader(number: usize, validators: usize) -> u32 {
+	(number % validators) as u32
+}
+
+#[test]
+fn validators_author_in_turn() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	sim.start_all();
+	sim.run_for(Duration::from_secs(20));
+
+	sim.assert_converged(&[0, 1, 2]);
+	assert!(sim.best_number(0) >= 15, "only {} blocks in 20s", sim.best_number(0));
+	for (index, pre_digest) in sim.best_chain(0).iter().enumerate() {
+		assert_eq!(pre_digest.authority_index, leader(index + 1, 3));
+		assert_eq!(pre_digest.skipped, 0);
+	}
+	assert_eq!(sim.client(0).leaves(), 1, "no forks without faults");
+	assert_eq!(sim.rejected(), [], "blocks were rejected without faults");
+}
+
+#[test]
+fn weighted_shuffled_turns_are_followed_and_verified() {
+	let config =
+		ChainConfig { weights: vec![2, 1, 1], epoch_length: Some(4), ..ChainConfig::new(3) };
+	let mut sim = Simulation::new(config.clone());
+	sim.start_all();
+	sim.run_for(Duration::from_secs(30));
+
+	sim.assert_converged(&[0, 1, 2]);
+	assert_eq!(sim.rejected(), [], "the verifier rejected blocks authored in turn");
+	let best_chain = sim.best_chain(0);
+	assert!(best_chain.len() >= 20, "only {} blocks in 30s", best_chain.len());
+	for pre_digest in &best_chain {
+		let schedule = config.schedule(pre_digest.slot);
+		assert_eq!(Some(pre_digest.authority_index), scheduled_author(pre_digest.slot, &schedule));
+		assert_eq!(pre_digest.skipped, 0);
+	}
+	let orders: Vec<_> = (0..8).map(|epoch| config.schedule((epoch * 4).into())).collect();
+	assert!(orders.windows(2).any(|pair| pair[0] != pair[1]), "the schedule is never shuffled");
+	// Validator 0 has half the weight, give or take the epochs the chain only partly covers
+	let by_heaviest =
+		best_chain.iter().filter(|pre_digest| pre_digest.authority_index == 0).count();
+	assert!(by_heaviest.abs_diff(best_chain.len() / 2) <= 2, "turns do not follow the weights");
+}
+
+#[test]
+fn blocks_out_of_turn_are_rejected() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	let genesis = sim.client(0).expect_header(sim.best_hash(0));
+
+	// Block #1 is validator 1's, which validator 2 may only take after the missed-turn timeout
+	let early = sim.author_block(2, &genesis, 1);
+	let error = sim.import(0, early).unwrap_err();
+	assert!(error.contains("before their missed-turn timeout"), "{}", error);
+	let impostor = sim.author_block(2, &genesis, 0);
+	let error = sim.import(0, impostor).unwrap_err();
+	assert!(error.contains("claims author 2 but validator 1 is scheduled"), "{}", error);
+
+	sim.run_for(Duration::from_secs(3));
+	let fallback = sim.author_block(2, &genesis, 1);
+	assert!(sim.import(0, fallback).is_ok());
+	assert_eq!(sim.best_number(0), 1);
+}
+
+#[test]
+fn blocks_too_close_to_their_parent_are_rejected() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	let genesis = sim.client(0).expect_header(sim.best_hash(0));
+	sim.run_for(Duration::from_secs(1));
+	let first = sim.author_block(1, &genesis, 0);
+	sim.import(0, first.clone()).unwrap();
+
+	// Block #2 must come at least half the 1s block time after block #1
+	sim.run_for(Duration::from_millis(400));
+	let early = sim.author_block(2, &first.header, 0);
+	let error = sim.import(0, early).unwrap_err();
+	assert!(error.contains("less than the minimum period after its parent's"), "{}", error);
+
+	sim.run_for(Duration::from_millis(100));
+	let second = sim.author_block(2, &first.header, 0);
+	assert!(sim.import(0, second).is_ok());
+	assert_eq!(sim.best_number(0), 2);
+}
+
+#[test]
+fn crashed_validator_is_skipped_until_it_restarts() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	sim.start_all();
+	sim.run_for(Duration::from_secs(5));
+	let crashed_at = sim.best_number(0) as usize;
+
+	sim.crash(1);
+	sim.run_for(Duration::from_secs(30));
+
+	sim.assert_converged(&[0, 2]);
+	assert!(sim.best_number(0) as usize >= crashed_at + 10, "the chain stalled");
+	assert!(sim.best_number(1) as usize <= crashed_at, "a crashed validator got blocks");
+	for (index, pre_digest) in sim.best_chain(0).iter().enumerate().skip(crashed_at) {
+		assert_ne!(pre_digest.authority_index, 1, "a crashed validator authored");
+		if leader(index + 1, 3) == 1 {
+			// The next validator in line takes over once the missed-turn timeout passes
+			assert_eq!((pre_digest.authority_index, pre_digest.skipped), (2, 1));
+		}
+	}
+
+	sim.restart(1);
+	let restarted_at = sim.best_number(0) as usize;
+	sim.run_for(Duration::from_secs(15));
+
+	sim.assert_converged(&[0, 1, 2]);
+	let back_in_turn = sim
+		.best_chain(0)
+		.iter()
+		.skip(restarted_at)
+		.any(|pre_digest| pre_digest.authority_index == 1 && pre_digest.skipped == 0);
+	assert!(back_in_turn, "the restarted validator never authored on its turn");
+}
+
+#[test]
+fn validator_retries_after_failing_to_build() {
+	// Without a missed-turn timeout nobody else may author in place of the failing validator
+	let mut sim = Simulation::new(ChainConfig { missed_turn_timeout: None, ..ChainConfig::new(3) });
+	sim.start_all();
+	sim.run_for(Duration::from_secs(5));
+	let failed_at = sim.best_number(0) as usize;
+
+	sim.fail_builds(1, 2);
+	sim.run_for(Duration::from_secs(15));
+
+	sim.assert_converged(&[0, 1, 2]);
+	assert!(sim.best_number(0) as usize >= failed_at + 6, "the chain stalled");
+	for (index, pre_digest) in sim.best_chain(0).iter().enumerate() {
+		assert_eq!(pre_digest.authority_index, leader(index + 1, 3));
+	}
+}
+
+#[test]
+fn majority_side_of_a_partition_wins_after_healing() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	sim.start_all();
+	sim.run_for(Duration::from_secs(3));
+	let split_at = sim.best_number(0);
+
+	sim.partition(&[&[0, 1], &[2]]);
+	sim.run_for(Duration::from_secs(30));
+
+	sim.assert_converged(&[0, 1]);
+	assert_ne!(sim.best_hash(0), sim.best_hash(2), "the sides of a partition agree");
+	assert!(sim.best_number(2) > split_at, "the minority side stalled");
+	assert!(
+		sim.best_number(0) > sim.best_number(2),
+		"the majority side should author faster, skipping one validator instead of two"
+	);
+	let minority_best = sim.best_hash(2);
+
+	sim.heal();
+	sim.run_for(Duration::from_secs(10));
+
+	sim.assert_converged(&[0, 1, 2]);
+	let common_chain = sim.client(2).best_chain();
+	assert!(
+		common_chain.iter().all(|header| header.hash() != minority_best),
+		"the minority fork was kept"
+	);
+	assert!(sim.client(0).leaves() >= 2, "the minority fork was not imported as a fork");
+}
+
+#[test]
+fn late_blocks_lose_to_fallback_blocks() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	sim.start_all();
+	sim.run_for(Duration::from_secs(3));
+	let delayed_at = sim.best_number(1) as usize;
+
+	// Validator 0's blocks arrive after the missed-turn timeout of 3s
+	sim.delay(0, Duration::from_secs(5));
+	sim.run_for(Duration::from_secs(30));
+
+	let taken_over = sim
+		.best_chain(1)
+		.iter()
+		.enumerate()
+		.skip(delayed_at)
+		.filter(|(index, pre_digest)| {
+			leader(index + 1, 3) == 0 && pre_digest.authority_index == 1 && pre_digest.skipped == 1
+		})
+		.count();
+	assert!(taken_over >= 2, "only {} turns of the late validator were taken over", taken_over);
+	assert!(sim.client(1).leaves() >= 2, "the late blocks were not imported as forks");
+
+	sim.delay(0, Duration::ZERO);
+	sim.run_for(Duration::from_secs(10));
+
+	sim.assert_converged(&[0, 1, 2]);
+}
+
+#[test]
+fn slots_give_turns_by_time() {
+	let mut sim = Simulation::new(ChainConfig {
+		slot_duration: Some(2_000),
+		missed_turn_timeout: None,
+		..ChainConfig::new(3)
+	});
+	sim.start_all();
+	sim.run_for(Duration::from_secs(10));
+	sim.crash(2);
+	sim.run_for(Duration::from_secs(20));
+
+	sim.assert_converged(&[0, 1]);
+	let best_chain = sim.best_chain(0);
+	assert!(best_chain.len() >= 8, "only {} blocks in 30s", best_chain.len());
+	for pre_digest in &best_chain {
+		assert_eq!(pre_digest.authority_index, (*pre_digest.slot % 3) as u32);
+	}
+	// The slots of the crashed validator go empty
+	assert!(best_chain.windows(2).any(|pair| *pair[1].slot > *pair[0].slot + 1));
+}
+
+#[test]
+fn paused_validator_is_skipped_until_resumed() {
+	let mut sim = Simulation::new(ChainConfig::new(3));
+	sim.start_all();
+	sim.run_for(Duration::from_secs(5));
+	let paused_at = sim.best_number(0) as usize;
+
+	sim.authoring(1).pause();
+	sim.run_for(Duration::from_secs(20));
+
+	// A paused validator keeps following the chain
+	sim.assert_converged(&[0, 1, 2]);
+	assert!(sim.best_number(0) as usize >= paused_at + 6, "the chain stalled");
+	let authored_while_paused = sim
+		.best_chain(0)
+		.iter()
+		.skip(paused_at)
+		.any(|pre_digest| pre_digest.authority_index == 1);
+	assert!(!authored_while_paused, "a paused validator authored");
+
+	sim.authoring(1).resume();
+	let resumed_at = sim.best_number(0) as usize;
+	sim.run_for(Duration::from_secs(10));
+
+	sim.assert_converged(&[0, 1, 2]);
+	let back_in_turn = sim
+		.best_chain(0)
+		.iter()
+		.skip(resumed_at)
+		.any(|pre_digest| pre_digest.authority_index == 1 && pre_digest.skipped == 0);
+	assert!(back_in_turn, "the resumed validator never authored on its turn");
+}
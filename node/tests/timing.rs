@@ -0,0 +1,52 @@
+//! Checks of the round-robin timing of a validator against the timing of its chain.
+
+use minimal_template_node::consensus::{ChainTiming, RoundRobinTiming};
+use std::time::Duration;
+
+fn millis(millis: u64) -> Option<Duration> {
+	Some(Duration::from_millis(millis))
+}
+
+#[test]
+fn timing_fits_chains_without_constraints() {
+	assert_eq!(RoundRobinTiming::default().validate_for(&ChainTiming::default()), Ok(()));
+}
+
+#[test]
+fn block_time_may_not_be_below_the_chain_block_time() {
+	let chain = ChainTiming { block_time: millis(2_000), ..Default::default() };
+	let error = RoundRobinTiming::new(Duration::from_secs(1)).validate_for(&chain).unwrap_err();
+	assert!(error.contains("is below the chain's"), "{}", error);
+	assert_eq!(RoundRobinTiming::new(Duration::from_secs(2)).validate_for(&chain), Ok(()));
+}
+
+#[test]
+fn turn_must_fit_in_a_slot() {
+	// A 1s block time and its 500ms proposal deadline fill a 1.5s slot exactly
+	let timing = RoundRobinTiming::default();
+	let fits = ChainTiming { slot_duration: millis(1_500), ..Default::default() };
+	assert_eq!(timing.validate_for(&fits), Ok(()));
+
+	let too_short = ChainTiming { slot_duration: millis(1_499), ..Default::default() };
+	let error = timing.validate_for(&too_short).unwrap_err();
+	assert!(error.contains("must not exceed the chain's slot duration"), "{}", error);
+
+	// The deadline alone fits in the slot, but not after waiting out the block time
+	let timing = RoundRobinTiming {
+		block_time: Duration::from_secs(6),
+		proposal_deadline: Duration::from_secs(1),
+	};
+	let slots = ChainTiming { slot_duration: millis(6_000), ..Default::default() };
+	assert!(timing.validate_for(&slots).is_err());
+}
+
+#[test]
+fn missed_turn_timeout_must_exceed_a_turn() {
+	let timing = RoundRobinTiming::default();
+	let too_short = ChainTiming { missed_turn_timeout: millis(1_500), ..Default::default() };
+	let error = timing.validate_for(&too_short).unwrap_err();
+	assert!(error.contains("take over turns that are not missed"), "{}", error);
+
+	let exceeds = ChainTiming { missed_turn_timeout: millis(1_501), ..Default::default() };
+	assert_eq!(timing.validate_for(&exceeds), Ok(()));
+}
@@ -0,0 +1,294 @@
+//! Proof-of-work difficulty.
+//!
+//! Stores the difficulty blocks must be mined at and retargets it every `AdjustmentPeriod` blocks,
+//! scaling it by how much faster or slower than `TargetBlockTime` the blocks of the period arrived
+//! according to `pallet_timestamp`. A single retarget changes the difficulty by at most
+//! `MaxAdjustmentFactor` in either direction, and never below `MinimumDifficulty`.
+//!
+//! The node's proof-of-work algorithm reads the difficulty at the parent of the block being mined
+//! or imported through [`sp_consensus_pow::DifficultyApi`].
+//!
+//! [`sp_consensus_pow::DifficultyApi`]: polkadot_sdk::sp_consensus_pow::DifficultyApi
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame::{deps::sp_runtime::SaturatedConversion, prelude::*};
+use polkadot_sdk::{pallet_timestamp, polkadot_sdk_frame as frame, sp_core::U256};
+
+// Re-export all pallet parts, this is needed to properly import the pallet into the runtime.
+pub use pallet::*;
+
+#[frame::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::config]
+	pub trait Config: polkadot_sdk::frame_system::Config + pallet_timestamp::Config {
+		/// The overarching runtime event type.
+		type RuntimeEvent: From<Event<Self>>
+			+ IsType<<Self as polkadot_sdk::frame_system::Config>::RuntimeEvent>;
+	}
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The difficulty was retargeted at the end of an adjustment period.
+		DifficultyAdjusted { previous: U256, difficulty: U256 },
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_: BlockNumberFor<T>) -> Weight {
+			// Accounts for the retarget in `on_finalize`
+			T::DbWeight::get().reads_writes(6, 2)
+		}
+
+		fn on_finalize(n: BlockNumberFor<T>) {
+			let now = pallet_timestamp::Pallet::<T>::get().saturated_into::<u64>();
+
+			// The genesis block has no timestamp, so the first period starts at block #1
+			let Some((start_block, start_time)) = PeriodStart::<T>::get() else {
+				PeriodStart::<T>::put((n, now));
+				return;
+			};
+
+			let blocks = (n - start_block).saturated_into::<u64>();
+			if blocks < AdjustmentPeriod::<T>::get() as u64 {
+				return;
+			}
+
+			let previous = CurrentDifficulty::<T>::get();
+			let expected = TargetBlockTime::<T>::get().saturating_mul(blocks);
+			let difficulty = Self::retarget(previous, expected, now.saturating_sub(start_time));
+
+			CurrentDifficulty::<T>::put(difficulty);
+			PeriodStart::<T>::put((n, now));
+			Self::deposit_event(Event::DifficultyAdjusted { previous, difficulty });
+		}
+	}
+
+	/// The difficulty the next block must be mined at.
+	#[pallet::storage]
+	pub type CurrentDifficulty<T: Config> = StorageValue<_, U256, ValueQuery>;
+
+	/// The difficulty never drops below this.
+	#[pallet::storage]
+	pub type MinimumDifficulty<T: Config> = StorageValue<_, U256, ValueQuery>;
+
+	/// The desired average time between blocks, in milliseconds.
+	#[pallet::storage]
+	pub type TargetBlockTime<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+	/// The number of blocks between retargets.
+	#[pallet::storage]
+	pub type AdjustmentPeriod<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+	/// The largest factor by which a single retarget may raise or lower the difficulty.
+	#[pallet::storage]
+	pub type MaxAdjustmentFactor<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+	/// The block number and timestamp at which the current adjustment period started.
+	#[pallet::storage]
+	pub type PeriodStart<T: Config> = StorageValue<_, (BlockNumberFor<T>, u64), OptionQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config> {
+		/// The difficulty of the first blocks.
+		pub initial_difficulty: U256,
+		/// The difficulty never drops below this.
+		pub minimum_difficulty: U256,
+		/// The desired average time between blocks, in milliseconds.
+		pub target_block_time: u64,
+		/// The number of blocks between retargets.
+		pub adjustment_period: u32,
+		/// The largest factor by which a single retarget may raise or lower the difficulty.
+		pub max_adjustment_factor: u32,
+		#[serde(skip)]
+		pub _config: core::marker::PhantomData<T>,
+	}
+
+	impl<T: Config> Default for GenesisConfig<T> {
+		fn default() -> Self {
+			Self {
+				initial_difficulty: U256::from(4_000_000),
+				minimum_difficulty: U256::from(1_000),
+				target_block_time: 6_000,
+				adjustment_period: 10,
+				max_adjustment_factor: 4,
+				_config: Default::default(),
+			}
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+		fn build(&self) {
+			assert!(self.target_block_time > 0, "Target block time must be positive");
+			assert!(self.adjustment_period > 0, "Adjustment period must be positive");
+			assert!(self.max_adjustment_factor > 0, "Max adjustment factor must be positive");
+			// A zero difficulty accepts any seal and can never be retargeted back up
+			assert!(!self.minimum_difficulty.is_zero(), "Minimum difficulty must be positive");
+			assert!(
+				self.initial_difficulty >= self.minimum_difficulty,
+				"Initial difficulty must not be below the minimum difficulty"
+			);
+
+			CurrentDifficulty::<T>::put(self.initial_difficulty);
+			MinimumDifficulty::<T>::put(self.minimum_difficulty);
+			TargetBlockTime::<T>::put(self.target_block_time);
+			AdjustmentPeriod::<T>::put(self.adjustment_period);
+			MaxAdjustmentFactor::<T>::put(self.max_adjustment_factor);
+		}
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The difficulty the next block must be mined at.
+	pub fn difficulty() -> U256 {
+		CurrentDifficulty::<T>::get()
+	}
+
+	/// Scales `current` by `expected / actual` milliseconds, bounded by the maximum adjustment
+	/// factor and the minimum difficulty.
+	fn retarget(current: U256, expected: u64, actual: u64) -> U256 {
+		let factor = U256::from(MaxAdjustmentFactor::<T>::get().max(1));
+		let adjusted = current.saturating_mul(U256::from(expected)) / U256::from(actual.max(1));
+
+		adjusted
+			.clamp(current / factor, current.saturating_mul(factor))
+			.max(MinimumDifficulty::<T>::get())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame::testing_prelude::*;
+
+	type Block = MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test {
+			System: frame_system,
+			Timestamp: pallet_timestamp,
+			Difficulty: crate,
+		}
+	);
+
+	#[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+	impl frame_system::Config for Test {
+		type Block = Block;
+	}
+
+	impl pallet_timestamp::Config for Test {
+		type Moment = u64;
+		type OnTimestampSet = ();
+		type MinimumPeriod = ConstU64<1>;
+		type WeightInfo = ();
+	}
+
+	impl Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+	}
+
+	/// A period of 10 blocks targeting 6s each, retargeting by at most 4x.
+	fn new_test_ext() -> TestState {
+		let storage = RuntimeGenesisConfig {
+			difficulty: GenesisConfig {
+				initial_difficulty: U256::from(1_000_000),
+				minimum_difficulty: U256::from(100_000),
+				..Default::default()
+			},
+			..Default::default()
+		}
+		.build_storage()
+		.unwrap();
+		let mut ext = TestState::new(storage);
+		ext.execute_with(|| System::set_block_number(1));
+		ext
+	}
+
+	/// Finalizes blocks #1 to #`last`, the first at 0ms and the others `block_time` ms apart.
+	fn run_blocks(last: u64, block_time: u64) {
+		for n in 1..=last {
+			System::set_block_number(n);
+			Timestamp::set_timestamp((n - 1) * block_time);
+			Difficulty::on_finalize(n);
+			Timestamp::on_finalize(n);
+		}
+	}
+
+	#[test]
+	fn difficulty_holds_within_a_period() {
+		new_test_ext().execute_with(|| {
+			run_blocks(10, 1_000);
+
+			assert_eq!(Difficulty::difficulty(), U256::from(1_000_000));
+			assert_eq!(PeriodStart::<Test>::get(), Some((1, 0)));
+		});
+	}
+
+	#[test]
+	fn fast_blocks_raise_the_difficulty() {
+		new_test_ext().execute_with(|| {
+			run_blocks(11, 3_000);
+
+			assert_eq!(Difficulty::difficulty(), U256::from(2_000_000));
+			assert_eq!(PeriodStart::<Test>::get(), Some((11, 30_000)));
+			System::assert_last_event(
+				Event::DifficultyAdjusted {
+					previous: U256::from(1_000_000),
+					difficulty: U256::from(2_000_000),
+				}
+				.into(),
+			);
+		});
+	}
+
+	#[test]
+	fn slow_blocks_lower_the_difficulty() {
+		new_test_ext().execute_with(|| {
+			run_blocks(11, 12_000);
+
+			assert_eq!(Difficulty::difficulty(), U256::from(500_000));
+		});
+	}
+
+	#[test]
+	fn retargets_are_bounded_by_the_max_adjustment_factor() {
+		new_test_ext().execute_with(|| {
+			run_blocks(11, 100);
+			assert_eq!(Difficulty::difficulty(), U256::from(4_000_000));
+		});
+		new_test_ext().execute_with(|| {
+			run_blocks(11, 60_000);
+			assert_eq!(Difficulty::difficulty(), U256::from(250_000));
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Minimum difficulty must be positive")]
+	fn genesis_rejects_a_zero_minimum_difficulty() {
+		let _ = RuntimeGenesisConfig {
+			difficulty: GenesisConfig {
+				initial_difficulty: U256::from(1_000_000),
+				minimum_difficulty: U256::zero(),
+				..Default::default()
+			},
+			..Default::default()
+		}
+		.build_storage();
+	}
+
+	#[test]
+	fn difficulty_never_drops_below_the_minimum() {
+		new_test_ext().execute_with(|| {
+			run_blocks(21, 60_000);
+
+			assert_eq!(Difficulty::difficulty(), U256::from(100_000));
+		});
+	}
+}
@@ -0,0 +1,263 @@
+//! Block rewards.
+//!
+//! Pays the author of every block a newly minted subsidy, starting at `InitialSubsidy` and halving
+//! every `HalvingInterval` blocks, and routes the transaction fees of the block to its author
+//! through the pallet's [`OnUnbalanced`] implementation.
+//!
+//! The author is identified from the pre-runtime digests of the header by `FindAuthor`, so any
+//! consensus engine naming its authors there can be rewarded. Blocks without an identifiable
+//! author mint no subsidy, and their fees are burned.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame::{
+	deps::sp_runtime::{traits::Zero, SaturatedConversion},
+	prelude::*,
+	traits::{
+		fungible::{self, Balanced, Mutate},
+		FindAuthor, Imbalance, OnUnbalanced,
+	},
+};
+use polkadot_sdk::polkadot_sdk_frame as frame;
+
+// Re-export all pallet parts, this is needed to properly import the pallet into the runtime.
+pub use pallet::*;
+
+/// The balance type of the rewarded currency.
+pub type BalanceOf<T> = <<T as Config>::Currency as fungible::Inspect<
+	<T as polkadot_sdk::frame_system::Config>::AccountId,
+>>::Balance;
+
+/// Funds taken out of circulation, such as transaction fees.
+pub type CreditOf<T> =
+	fungible::Credit<<T as polkadot_sdk::frame_system::Config>::AccountId, <T as Config>::Currency>;
+
+#[frame::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::config]
+	pub trait Config: polkadot_sdk::frame_system::Config {
+		/// The overarching runtime event type.
+		type RuntimeEvent: From<Event<Self>>
+			+ IsType<<Self as polkadot_sdk::frame_system::Config>::RuntimeEvent>;
+
+		/// The currency rewards are paid in.
+		type Currency: Mutate<Self::AccountId> + Balanced<Self::AccountId>;
+
+		/// Identifies the author of a block from its pre-runtime digests.
+		type FindAuthor: FindAuthor<Self::AccountId>;
+
+		/// The subsidy of the blocks before the first halving.
+		#[pallet::constant]
+		type InitialSubsidy: Get<BalanceOf<Self>>;
+
+		/// The number of blocks between halvings of the subsidy. Zero disables halving.
+		#[pallet::constant]
+		type HalvingInterval: Get<BlockNumberFor<Self>>;
+	}
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The author of the block was paid the block subsidy.
+		SubsidyPaid { author: T::AccountId, subsidy: BalanceOf<T> },
+		/// The author of the block was paid the fees of a transaction.
+		FeesPaid { author: T::AccountId, fees: BalanceOf<T> },
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_: BlockNumberFor<T>) -> Weight {
+			let digest = polkadot_sdk::frame_system::Pallet::<T>::digest();
+			let pre_runtime_digests = digest.logs().iter().filter_map(|d| d.as_pre_runtime());
+			if let Some(author) = T::FindAuthor::find_author(pre_runtime_digests) {
+				Author::<T>::put(author);
+			}
+
+			// Accounts for minting the subsidy in `on_finalize`
+			T::DbWeight::get().reads_writes(3, 3)
+		}
+
+		fn on_finalize(n: BlockNumberFor<T>) {
+			let Some(author) = Author::<T>::take() else {
+				return;
+			};
+
+			let subsidy = Self::subsidy(n);
+			if subsidy.is_zero() {
+				return;
+			}
+
+			// A subsidy below the existential deposit of a new account is forfeited
+			if T::Currency::mint_into(&author, subsidy).is_ok() {
+				Self::deposit_event(Event::SubsidyPaid { author, subsidy });
+			}
+		}
+	}
+
+	/// The author of the current block. Only present during block execution.
+	#[pallet::storage]
+	pub type Author<T: Config> = StorageValue<_, T::AccountId, OptionQuery>;
+}
+
+impl<T: Config> Pallet<T> {
+	/// The author of the current block, if it could be identified.
+	pub fn author() -> Option<T::AccountId> {
+		Author::<T>::get()
+	}
+
+	/// The subsidy paid for block `n`.
+	pub fn subsidy(n: BlockNumberFor<T>) -> BalanceOf<T> {
+		let interval = T::HalvingInterval::get();
+		let halvings = if interval.is_zero() { 0 } else { (n / interval).saturated_into::<u32>() };
+
+		// Balances are at most 128 bits wide, so the subsidy is zero after 128 halvings
+		(0..halvings.min(128)).fold(T::InitialSubsidy::get(), |subsidy, _| subsidy / 2u32.into())
+	}
+}
+
+impl<T: Config> OnUnbalanced<CreditOf<T>> for Pallet<T> {
+	fn on_nonzero_unbalanced(amount: CreditOf<T>) {
+		// Dropping the credit burns the fees
+		let Some(author) = Author::<T>::get() else {
+			return;
+		};
+
+		let fees = amount.peek();
+		if T::Currency::resolve(&author, amount).is_ok() {
+			Self::deposit_event(Event::FeesPaid { author, fees });
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame::{
+		deps::sp_runtime::{ConsensusEngineId, Digest, DigestItem},
+		testing_prelude::*,
+	};
+	use polkadot_sdk::pallet_balances;
+
+	type Block = MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test {
+			System: frame_system,
+			Balances: pallet_balances,
+			Rewards: crate,
+		}
+	);
+
+	#[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+	impl frame_system::Config for Test {
+		type Block = Block;
+		type AccountData = pallet_balances::AccountData<u64>;
+	}
+
+	#[derive_impl(pallet_balances::config_preludes::TestDefaultConfig)]
+	impl pallet_balances::Config for Test {
+		type AccountStore = System;
+	}
+
+	/// The engine naming block authors in the test digests.
+	const ENGINE_ID: ConsensusEngineId = *b"test";
+
+	/// Reads the author account from the pre-runtime digest of [`ENGINE_ID`].
+	pub struct DigestAuthor;
+
+	impl FindAuthor<u64> for DigestAuthor {
+		fn find_author<'a, I>(digests: I) -> Option<u64>
+		where
+			I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+		{
+			digests
+				.into_iter()
+				.find(|(id, _)| *id == ENGINE_ID)
+				.and_then(|(_, mut data)| u64::decode(&mut data).ok())
+		}
+	}
+
+	impl Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type Currency = Balances;
+		type FindAuthor = DigestAuthor;
+		type InitialSubsidy = ConstU64<1_000>;
+		type HalvingInterval = ConstU64<10>;
+	}
+
+	fn new_test_ext() -> TestState {
+		let storage = RuntimeGenesisConfig::default().build_storage().unwrap();
+		TestState::new(storage)
+	}
+
+	/// Starts block `n`, authored by `author` if given.
+	fn start_block(n: u64, author: Option<u64>) {
+		let logs = author
+			.map(|author| DigestItem::PreRuntime(ENGINE_ID, author.encode()))
+			.into_iter()
+			.collect();
+		System::initialize(&n, &Default::default(), &Digest { logs });
+		Rewards::on_initialize(n);
+	}
+
+	#[test]
+	fn subsidy_halves_every_interval() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Rewards::subsidy(1), 1_000);
+			assert_eq!(Rewards::subsidy(9), 1_000);
+			assert_eq!(Rewards::subsidy(10), 500);
+			assert_eq!(Rewards::subsidy(25), 250);
+			assert_eq!(Rewards::subsidy(10_000), 0);
+		});
+	}
+
+	#[test]
+	fn author_is_paid_the_subsidy() {
+		new_test_ext().execute_with(|| {
+			start_block(12, Some(7));
+			assert_eq!(Rewards::author(), Some(7));
+
+			Rewards::on_finalize(12);
+			assert_eq!(Balances::free_balance(7), 500);
+			assert_eq!(Rewards::author(), None);
+			System::assert_last_event(Event::SubsidyPaid { author: 7, subsidy: 500 }.into());
+		});
+	}
+
+	#[test]
+	fn blocks_without_an_author_mint_nothing() {
+		new_test_ext().execute_with(|| {
+			start_block(1, None);
+			Rewards::on_finalize(1);
+
+			assert_eq!(Balances::total_issuance(), 0);
+			assert!(System::events().is_empty());
+		});
+	}
+
+	#[test]
+	fn fees_go_to_the_author() {
+		new_test_ext().execute_with(|| {
+			start_block(1, Some(7));
+			Rewards::on_unbalanced(<Balances as Balanced<u64>>::issue(100));
+
+			assert_eq!(Balances::free_balance(7), 100);
+			System::assert_last_event(Event::FeesPaid { author: 7, fees: 100 }.into());
+		});
+	}
+
+	#[test]
+	fn fees_of_blocks_without_an_author_are_burned() {
+		new_test_ext().execute_with(|| {
+			start_block(1, None);
+			Rewards::on_unbalanced(<Balances as Balanced<u64>>::issue(100));
+
+			assert_eq!(Balances::total_issuance(), 0);
+		});
+	}
+}
@@ -0,0 +1,1250 @@
+//! Round-robin authority set.
+//!
+//! Stores the ordered list of authorities that take turns authoring blocks, and the slot
+//! duration if turns are given by wall-clock slots rather than block height. The node queries
+//! both through [`sp_consensus_round_robin::RoundRobinApi`] when authoring and when verifying
+//! imported blocks.
+//!
+//! Every block authored by round-robin must claim a slot later than its parent's. When turns are
+//! given by slots, the claimed slot must also be the one the block's timestamp falls in, which
+//! the pallet checks as the `OnTimestampSet` handler of `pallet_timestamp`.
+//!
+//! When turns are given by height and a missed-turn timeout is set, a block skipping authorities
+//! that missed their turn must be timestamped at least the timeout per skipped authority after its
+//! parent.
+//!
+//! The authority set is changed by `AuthorityOrigin` (e.g. sudo) and takes effect from the next
+//! block, since blocks are checked against the authority set of their parent.
+//!
+//! Each authority has a weight, one unless set otherwise by `AuthorityOrigin` (e.g. to its bonded
+//! stake). Authorities author a share of the slots proportional to their weight, following the
+//! [`Schedule`] recomputed whenever the authorities or their weights change.
+//!
+//! If an epoch length is set, the schedule is shuffled every epoch with a seed derived from the
+//! hashes of past blocks, which commit to their authors' seals. The seed of an epoch is fixed at
+//! the end of the epoch before the previous one, so leaders are only known one epoch in advance.
+//!
+//! The seed is not unbiased. The author of the last block before a seed is fixed can re-seal its
+//! block as often as it likes, sr25519 and ML-DSA signatures being randomized, and publish
+//! whichever seal gives the shuffle it prefers; it can also withhold its block, leaving the seed
+//! to the block built in its place. The shuffle only hides future leaders from outsiders, not
+//! from authorities willing to grind; unbiasable leader selection needs a VRF, as in BABE, or a
+//! commit-reveal scheme.
+//!
+//! Each authority may have a finality key, such as its GRANDPA key, set by `AuthorityOrigin` or in
+//! the genesis config. At the end of every block changing the authority set or its finality keys,
+//! `OnAuthoritiesChanged` is told the finality keys of the new set, so the finality voters follow
+//! the authorities.
+//!
+//! An authority sealing two different blocks for the same slot can be reported by anyone with an
+//! unsigned `report_equivocation` extrinsic carrying both headers. A valid report removes the
+//! offender from the authority set, unless it is the last authority.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use codec::{Decode, Encode};
+use frame::{
+	deps::{sp_core::hashing::blake2_256, sp_runtime::SaturatedConversion},
+	prelude::*,
+	traits::{ConstU64, FindAuthor, OnTimestampSet},
+};
+use polkadot_sdk::{
+	frame_system::offchain::{SendTransactionTypes, SubmitTransaction},
+	pallet_timestamp, polkadot_sdk_frame as frame,
+};
+use sp_consensus_round_robin::{
+	check_equivocation_proof, shuffle_schedule, weighted_schedule, AuthorityId, AuthorityIndex,
+	CompatibleDigestItem, EquivocationProof, PreDigest, SealScheme, Slot, ROUND_ROBIN_ENGINE_ID,
+};
+
+// Re-export all pallet parts, this is needed to properly import the pallet into the runtime.
+pub use pallet::*;
+
+#[frame::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::config]
+	pub trait Config:
+		polkadot_sdk::frame_system::Config
+		+ pallet_timestamp::Config
+		+ SendTransactionTypes<Call<Self>>
+	{
+		/// The overarching runtime event type.
+		type RuntimeEvent: From<Event<Self>>
+			+ IsType<<Self as polkadot_sdk::frame_system::Config>::RuntimeEvent>;
+
+		/// The origin allowed to change the authority set.
+		type AuthorityOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// The maximum number of authorities.
+		#[pallet::constant]
+		type MaxAuthorities: Get<u32>;
+
+		/// The maximum length of the schedule. Larger weights are scaled down to fit. Must exceed
+		/// `MaxAuthorities`.
+		#[pallet::constant]
+		type MaxScheduleLength: Get<u32>;
+
+		/// How many slots after an equivocation it may still be reported. Reported offences are
+		/// remembered for as long.
+		#[pallet::constant]
+		type ReportLongevity: Get<u64>;
+
+		/// The key an authority finalizes blocks with, such as its GRANDPA key.
+		type FinalityId: Member + Parameter + MaxEncodedLen + MaybeSerializeDeserialize;
+
+		/// Told the finality keys of the authority set whenever it changes.
+		type OnAuthoritiesChanged: OnAuthoritiesChanged<Self::FinalityId>;
+	}
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The authority set changed, effective from the next block.
+		AuthoritiesChanged { authorities: Vec<AuthorityId> },
+		/// The weight of an authority changed, effective from the next block.
+		WeightChanged { authority: AuthorityId, weight: u64 },
+		/// An authority sealed two blocks for the same slot and was removed from the authority
+		/// set, effective from the next block.
+		EquivocationReported { offender: AuthorityId, slot: Slot },
+		/// The finality key of an authority changed.
+		FinalityKeyChanged { authority: AuthorityId, key: T::FinalityId },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// The authority set would be empty, halting the chain.
+		NoAuthorities,
+		/// The authority set would exceed `MaxAuthorities`.
+		TooManyAuthorities,
+		/// An authority appears more than once.
+		DuplicateAuthority,
+		/// Not all authorities seal with the same scheme.
+		MixedSealSchemes,
+		/// The authority to remove is not in the set.
+		NotAnAuthority,
+		/// Every authority would have weight zero, halting the chain.
+		NoWeight,
+		/// The equivocation proof does not hold two different headers sealed by the offender for
+		/// its slot.
+		InvalidEquivocationProof,
+		/// The equivocation was already reported.
+		DuplicateOffenceReport,
+		/// The equivocation is more than `ReportLongevity` slots old.
+		StaleOffenceReport,
+		/// As many offences as there can be authorities were reported within `ReportLongevity`
+		/// slots; older ones must expire first.
+		TooManyOffenceReports,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Replaces the authority set.
+		#[pallet::call_index(0)]
+		#[pallet::weight(T::DbWeight::get().reads_writes(2 * T::MaxAuthorities::get() as u64, 3))]
+		pub fn set_authorities(
+			origin: OriginFor<T>,
+			authorities: Vec<AuthorityId>,
+		) -> DispatchResult {
+			T::AuthorityOrigin::ensure_origin(origin)?;
+			Self::change_authorities(authorities)
+		}
+
+		/// Appends `authority` to the authority set, making it the last to author in each round.
+		#[pallet::call_index(1)]
+		#[pallet::weight(
+			T::DbWeight::get().reads_writes(1 + 2 * T::MaxAuthorities::get() as u64, 3)
+		)]
+		pub fn add_authority(origin: OriginFor<T>, authority: AuthorityId) -> DispatchResult {
+			T::AuthorityOrigin::ensure_origin(origin)?;
+			let mut authorities = Self::authorities();
+			authorities.push(authority);
+			Self::change_authorities(authorities)
+		}
+
+		/// Removes `authority` from the authority set.
+		#[pallet::call_index(2)]
+		#[pallet::weight(
+			T::DbWeight::get().reads_writes(1 + 2 * T::MaxAuthorities::get() as u64, 3)
+		)]
+		pub fn remove_authority(origin: OriginFor<T>, authority: AuthorityId) -> DispatchResult {
+			T::AuthorityOrigin::ensure_origin(origin)?;
+			let mut authorities = Self::authorities();
+			let index = authorities
+				.iter()
+				.position(|a| *a == authority)
+				.ok_or(Error::<T>::NotAnAuthority)?;
+			authorities.remove(index);
+			Self::change_authorities(authorities)
+		}
+
+		/// Sets the weight of `authority`, which need not be in the authority set yet.
+		#[pallet::call_index(3)]
+		#[pallet::weight(T::DbWeight::get().reads_writes(2 + T::MaxAuthorities::get() as u64, 2))]
+		pub fn set_weight(
+			origin: OriginFor<T>,
+			authority: AuthorityId,
+			weight: u64,
+		) -> DispatchResult {
+			T::AuthorityOrigin::ensure_origin(origin)?;
+			AuthorityWeights::<T>::insert(&authority, weight);
+			let schedule = Self::compute_schedule(&Self::authorities());
+			ensure!(!schedule.is_empty(), Error::<T>::NoWeight);
+			Schedule::<T>::put(schedule);
+			Self::deposit_event(Event::WeightChanged { authority, weight });
+			Ok(())
+		}
+
+		/// Sets the key `authority` finalizes blocks with, which need not be in the authority set
+		/// yet.
+		#[pallet::call_index(5)]
+		#[pallet::weight(T::DbWeight::get().reads_writes(2 + T::MaxAuthorities::get() as u64, 2))]
+		pub fn set_finality_key(
+			origin: OriginFor<T>,
+			authority: AuthorityId,
+			key: T::FinalityId,
+		) -> DispatchResult {
+			T::AuthorityOrigin::ensure_origin(origin)?;
+			FinalityKeys::<T>::insert(&authority, &key);
+			if Authorities::<T>::get().contains(&authority) {
+				FinalityKeysChanged::<T>::put(true);
+			}
+			Self::deposit_event(Event::FinalityKeyChanged { authority, key });
+			Ok(())
+		}
+
+		/// Reports an authority that sealed two different blocks for the same slot, removing it
+		/// from the authority set.
+		///
+		/// Unsigned: the proof is checked by its signatures alone.
+		#[pallet::call_index(4)]
+		#[pallet::weight(
+			// Accounts for verifying both seals
+			T::DbWeight::get().reads_writes(2 + 2 * T::MaxAuthorities::get() as u64, 4)
+				.saturating_add(Weight::from_parts(100_000_000, 0))
+		)]
+		pub fn report_equivocation(
+			origin: OriginFor<T>,
+			equivocation_proof: Box<EquivocationProof<HeaderFor<T>>>,
+		) -> DispatchResult {
+			ensure_none(origin)?;
+			Self::check_report(&equivocation_proof)?;
+
+			let EquivocationProof { offender, slot, .. } = *equivocation_proof;
+			let mut reported = ReportedOffences::<T>::get();
+			let oldest = Self::oldest_reportable_slot();
+			reported.retain(|(_, reported_slot)| *reported_slot >= oldest);
+			reported
+				.try_push((offender.clone(), slot))
+				.map_err(|_| Error::<T>::TooManyOffenceReports)?;
+
+			let mut authorities = Self::authorities();
+			authorities.retain(|a| *a != offender);
+			Self::change_authorities(authorities)?;
+			ReportedOffences::<T>::put(reported);
+			Self::deposit_event(Event::EquivocationReported { offender, slot });
+			Ok(())
+		}
+	}
+
+	#[pallet::validate_unsigned]
+	impl<T: Config> ValidateUnsigned for Pallet<T> {
+		type Call = Call<T>;
+
+		fn validate_unsigned(source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+			let Call::report_equivocation { equivocation_proof } = call else {
+				return InvalidTransaction::Call.into();
+			};
+			// Reports are only submitted by the importing node itself, so none come from peers
+			if !matches!(source, TransactionSource::Local | TransactionSource::InBlock) {
+				return InvalidTransaction::Call.into();
+			}
+			Self::check_report(equivocation_proof).map_err(|_| InvalidTransaction::BadProof)?;
+
+			ValidTransaction::with_tag_prefix("RoundRobinEquivocation")
+				.priority(TransactionPriority::MAX)
+				.and_provides((equivocation_proof.offender.clone(), equivocation_proof.slot))
+				.longevity(T::ReportLongevity::get())
+				.propagate(false)
+				.build()
+		}
+
+		fn pre_dispatch(call: &Self::Call) -> Result<(), TransactionValidityError> {
+			let Call::report_equivocation { equivocation_proof } = call else {
+				return Err(InvalidTransaction::Call.into());
+			};
+			Self::check_report(equivocation_proof).map_err(|_| InvalidTransaction::BadProof.into())
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_: BlockNumberFor<T>) -> Weight {
+			if let Some(pre_digest) = Self::current_pre_digest() {
+				let current_slot = CurrentSlot::<T>::get();
+				assert!(current_slot < pre_digest.slot, "Slot must increase");
+				CurrentSlot::<T>::put(pre_digest.slot);
+				// Includes taking `FinalityKeysChanged` in `on_finalize`
+				let weight =
+					T::DbWeight::get().reads_writes(3, 2) + Self::advance_epoch(pre_digest.slot);
+
+				if pre_digest.skipped == 0 {
+					return weight;
+				}
+
+				// The block's timestamp is only set by its inherents, so it is checked on finalize
+				let deadline = Self::fallback_timestamp(pre_digest.skipped)
+					.expect("Missed turns are never skipped on this chain");
+				FallbackDeadline::<T>::put(deadline);
+
+				// Accounts for the check in `on_finalize`
+				weight + T::DbWeight::get().reads_writes(5, 2)
+			} else {
+				T::DbWeight::get().reads_writes(2, 1)
+			}
+		}
+
+		fn integrity_test() {
+			assert!(
+				T::MaxScheduleLength::get() > T::MaxAuthorities::get(),
+				"The schedule must fit every authority"
+			);
+		}
+
+		fn on_finalize(_: BlockNumberFor<T>) {
+			if let Some(deadline) = FallbackDeadline::<T>::take() {
+				let now = pallet_timestamp::Pallet::<T>::get().saturated_into::<u64>();
+				assert!(now >= deadline, "Missed-turn timeout has not elapsed");
+			}
+			if FinalityKeysChanged::<T>::take() {
+				T::OnAuthoritiesChanged::on_authorities_changed(Self::finality_keys());
+			}
+		}
+	}
+
+	/// The ordered round-robin authority set.
+	#[pallet::storage]
+	pub type Authorities<T: Config> =
+		StorageValue<_, BoundedVec<AuthorityId, T::MaxAuthorities>, ValueQuery>;
+
+	/// The weight of each authority. Authorities without one have weight one.
+	#[pallet::storage]
+	pub type AuthorityWeights<T: Config> =
+		StorageMap<_, Blake2_128Concat, AuthorityId, u64, ValueQuery, ConstU64<1>>;
+
+	/// The authoring schedule: the authority indices taking turns, slot after slot.
+	#[pallet::storage]
+	pub type Schedule<T: Config> =
+		StorageValue<_, BoundedVec<AuthorityIndex, T::MaxScheduleLength>, ValueQuery>;
+
+	/// The key each authority finalizes blocks with. Authorities without one do not finalize.
+	#[pallet::storage]
+	pub type FinalityKeys<T: Config> =
+		StorageMap<_, Blake2_128Concat, AuthorityId, T::FinalityId, OptionQuery>;
+
+	/// Whether the current block changed the authority set or its finality keys. Only present
+	/// during block execution.
+	#[pallet::storage]
+	pub type FinalityKeysChanged<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+	/// The offenders and slots of the equivocations reported in the last `ReportLongevity` slots.
+	/// Older ones are pruned on the next report.
+	#[pallet::storage]
+	pub type ReportedOffences<T: Config> =
+		StorageValue<_, BoundedVec<(AuthorityId, Slot), T::MaxAuthorities>, ValueQuery>;
+
+	/// The slot duration in milliseconds, or `None` if turns are given by block height.
+	#[pallet::storage]
+	pub type SlotDuration<T: Config> = StorageValue<_, u64, OptionQuery>;
+
+	/// The number of slots in an epoch, or `None` if the schedule is not shuffled.
+	#[pallet::storage]
+	pub type EpochLength<T: Config> = StorageValue<_, u64, OptionQuery>;
+
+	/// The epoch of the current block.
+	#[pallet::storage]
+	pub type CurrentEpoch<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+	/// The hash chain of all block hashes so far, each committing to its block's seal.
+	#[pallet::storage]
+	pub type AccumulatedRandomness<T: Config> = StorageValue<_, [u8; 32], ValueQuery>;
+
+	/// The seed of the current epoch's shuffle.
+	#[pallet::storage]
+	pub type EpochRandomness<T: Config> = StorageValue<_, [u8; 32], ValueQuery>;
+
+	/// The seed of the next epoch's shuffle, fixed at the end of the previous epoch.
+	#[pallet::storage]
+	pub type NextEpochRandomness<T: Config> = StorageValue<_, [u8; 32], ValueQuery>;
+
+	/// The slot of the current block.
+	#[pallet::storage]
+	pub type CurrentSlot<T: Config> = StorageValue<_, Slot, ValueQuery>;
+
+	/// How long in milliseconds after a block's timestamp the validator after the scheduled one may
+	/// author in its place, or `None` if missed turns are never skipped. Only used when turns are
+	/// given by height.
+	#[pallet::storage]
+	pub type MissedTurnTimeout<T: Config> = StorageValue<_, u64, OptionQuery>;
+
+	/// How long in milliseconds validators wait after a block before building on it, or `None` if
+	/// only the nodes know. Only used when turns are given by height.
+	#[pallet::storage]
+	pub type BlockTime<T: Config> = StorageValue<_, u64, OptionQuery>;
+
+	/// The earliest timestamp the current block may have, if it skips authorities that missed their
+	/// turn. Only present during block execution.
+	#[pallet::storage]
+	pub type FallbackDeadline<T: Config> = StorageValue<_, u64, OptionQuery>;
+
+	#[pallet::genesis_config]
+	#[derive(DefaultNoBound)]
+	pub struct GenesisConfig<T: Config> {
+		/// The initial authority set, in authoring order.
+		pub authorities: Vec<AuthorityId>,
+		/// The initial weights of authorities. Authorities not listed have weight one.
+		pub weights: Vec<(AuthorityId, u64)>,
+		/// The initial finality keys of authorities, which should match the initial finality
+		/// voters.
+		pub finality_keys: Vec<(AuthorityId, T::FinalityId)>,
+		/// The slot duration in milliseconds. Turns are given by block height if unset.
+		pub slot_duration: Option<u64>,
+		/// The missed-turn timeout in milliseconds. Missed turns are never skipped if unset.
+		pub missed_turn_timeout: Option<u64>,
+		/// The block time in milliseconds, when turns are given by height. Only the nodes know it
+		/// if unset.
+		pub block_time: Option<u64>,
+		/// The number of slots in an epoch. The schedule is not shuffled if unset.
+		pub epoch_length: Option<u64>,
+		#[serde(skip)]
+		pub _config: core::marker::PhantomData<T>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+		fn build(&self) {
+			for (authority, weight) in &self.weights {
+				AuthorityWeights::<T>::insert(authority, weight);
+			}
+			for (authority, key) in &self.finality_keys {
+				FinalityKeys::<T>::insert(authority, key);
+			}
+			Pallet::<T>::initialize_authorities(&self.authorities);
+			if let Some(slot_duration) = self.slot_duration {
+				assert!(slot_duration > 0, "Slot duration must be positive");
+				SlotDuration::<T>::put(slot_duration);
+			}
+			if let Some(missed_turn_timeout) = self.missed_turn_timeout {
+				assert!(missed_turn_timeout > 0, "Missed-turn timeout must be positive");
+				assert!(
+					self.slot_duration.is_none(),
+					"Missed turns are already skipped when turns are given by slots"
+				);
+				MissedTurnTimeout::<T>::put(missed_turn_timeout);
+			}
+			if let Some(block_time) = self.block_time {
+				assert!(block_time > 0, "Block time must be positive");
+				assert!(
+					self.slot_duration.is_none(),
+					"Blocks follow the slot duration when turns are given by slots"
+				);
+				assert!(
+					self.missed_turn_timeout.is_none_or(|timeout| timeout > block_time),
+					"Missed-turn timeout must exceed the block time"
+				);
+				BlockTime::<T>::put(block_time);
+			}
+			if let Some(epoch_length) = self.epoch_length {
+				assert!(epoch_length > 0, "Epoch length must be positive");
+				EpochLength::<T>::put(epoch_length);
+			}
+		}
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The current authority set, in authoring order.
+	pub fn authorities() -> Vec<AuthorityId> {
+		Authorities::<T>::get().into_inner()
+	}
+
+	/// The scheme the current authorities seal blocks with, or `None` without authorities.
+	pub fn seal_scheme() -> Option<SealScheme> {
+		Authorities::<T>::get().first().map(AuthorityId::scheme)
+	}
+
+	/// The authoring schedule for a block in `slot` built on the current block: the authority
+	/// indices taking turns, slot after slot.
+	///
+	/// When epochs are enabled, the schedule is shuffled with the seed of the current epoch, or of
+	/// the next one if `slot` is in a later epoch.
+	pub fn schedule(slot: Slot) -> Vec<AuthorityIndex> {
+		let mut schedule = Schedule::<T>::get().into_inner();
+		if let Some(epoch_length) = EpochLength::<T>::get() {
+			let seed = if *slot / epoch_length == CurrentEpoch::<T>::get() {
+				EpochRandomness::<T>::get()
+			} else {
+				NextEpochRandomness::<T>::get()
+			};
+			shuffle_schedule(&mut schedule, &seed);
+		}
+		schedule
+	}
+
+	/// The finality keys of the current authority set, in authoring order. Authorities without one
+	/// are left out.
+	pub fn finality_keys() -> Vec<T::FinalityId> {
+		Authorities::<T>::get().iter().filter_map(FinalityKeys::<T>::get).collect()
+	}
+
+	/// The slot duration, or `None` if turns are given by block height.
+	pub fn slot_duration() -> Option<sp_consensus_round_robin::SlotDuration> {
+		SlotDuration::<T>::get().map(sp_consensus_round_robin::SlotDuration::from_millis)
+	}
+
+	/// How long in milliseconds validators wait after a block before building on it, or `None` if
+	/// only the nodes know.
+	pub fn block_time() -> Option<u64> {
+		BlockTime::<T>::get()
+	}
+
+	/// How long in milliseconds after a block's timestamp the validator after the scheduled one may
+	/// author in its place, or `None` if missed turns are never skipped.
+	pub fn missed_turn_timeout() -> Option<u64> {
+		MissedTurnTimeout::<T>::get()
+	}
+
+	/// The minimum period between the timestamps of a block and its parent, in milliseconds: half
+	/// the slot duration, as with Aura, or half the block time when turns are given by height. One
+	/// millisecond, merely keeping timestamps increasing, if neither is set or the chain has no
+	/// round-robin blocks, as when another engine authors it.
+	pub fn minimum_period() -> u64 {
+		if !CurrentSlot::<T>::exists() {
+			return 1;
+		}
+		SlotDuration::<T>::get()
+			.or_else(BlockTime::<T>::get)
+			.map_or(1, |period| (period / 2).max(1))
+	}
+
+	/// The earliest timestamp at which a block skipping `skipped` authorities may be built on the
+	/// current block, or `None` if missed turns are never skipped.
+	pub fn fallback_timestamp(skipped: u32) -> Option<u64> {
+		if SlotDuration::<T>::exists() {
+			return None;
+		}
+		let timeout = MissedTurnTimeout::<T>::get()?;
+		let now = pallet_timestamp::Pallet::<T>::get().saturated_into::<u64>();
+		Some(now.saturating_add(timeout.saturating_mul(skipped as u64)))
+	}
+
+	/// Folds the parent hash into the accumulated randomness and, on the first block of an epoch,
+	/// rotates the epoch seeds.
+	///
+	/// The seed of an epoch is the randomness accumulated until the end of the epoch before the
+	/// previous one, so leaders are known one epoch in advance and no further. The author of the
+	/// last block folded into a seed can grind it, see the crate docs.
+	fn advance_epoch(slot: Slot) -> Weight {
+		let Some(epoch_length) = EpochLength::<T>::get() else {
+			return T::DbWeight::get().reads(1);
+		};
+
+		let parent_hash = polkadot_sdk::frame_system::Pallet::<T>::parent_hash();
+		let accumulated = blake2_256(&(AccumulatedRandomness::<T>::get(), parent_hash).encode());
+		AccumulatedRandomness::<T>::put(accumulated);
+
+		let epoch = *slot / epoch_length;
+		if epoch == CurrentEpoch::<T>::get() {
+			return T::DbWeight::get().reads_writes(4, 1);
+		}
+
+		CurrentEpoch::<T>::put(epoch);
+		EpochRandomness::<T>::put(NextEpochRandomness::<T>::get());
+		NextEpochRandomness::<T>::put(accumulated);
+		T::DbWeight::get().reads_writes(5, 4)
+	}
+
+	/// Submits an unsigned `report_equivocation` extrinsic to the transaction pool. Only works in
+	/// an offchain context, with the transaction pool extension registered.
+	pub fn submit_unsigned_equivocation_report(
+		equivocation_proof: EquivocationProof<HeaderFor<T>>,
+	) -> Option<()> {
+		let call = Call::report_equivocation { equivocation_proof: Box::new(equivocation_proof) };
+		SubmitTransaction::<T, Call<T>>::submit_unsigned_transaction(call.into()).ok()
+	}
+
+	/// The oldest slot an equivocation may still be reported for.
+	fn oldest_reportable_slot() -> Slot {
+		CurrentSlot::<T>::get().saturating_sub(T::ReportLongevity::get())
+	}
+
+	/// Checks that `equivocation_proof` is recent and valid, was not reported before and names a
+	/// current authority that can be removed.
+	fn check_report(equivocation_proof: &EquivocationProof<HeaderFor<T>>) -> DispatchResult {
+		ensure!(
+			equivocation_proof.slot >= Self::oldest_reportable_slot(),
+			Error::<T>::StaleOffenceReport
+		);
+		ensure!(
+			check_equivocation_proof(equivocation_proof, !SlotDuration::<T>::exists()),
+			Error::<T>::InvalidEquivocationProof
+		);
+		let offence = (equivocation_proof.offender.clone(), equivocation_proof.slot);
+		ensure!(
+			!ReportedOffences::<T>::get().contains(&offence),
+			Error::<T>::DuplicateOffenceReport
+		);
+		let authorities = Authorities::<T>::get();
+		ensure!(authorities.contains(&equivocation_proof.offender), Error::<T>::NotAnAuthority);
+		// Removing the last authority would halt the chain
+		ensure!(authorities.len() > 1, Error::<T>::NoAuthorities);
+		Ok(())
+	}
+
+	fn current_pre_digest() -> Option<PreDigest> {
+		polkadot_sdk::frame_system::Pallet::<T>::digest()
+			.logs()
+			.iter()
+			.find_map(|item| item.as_round_robin_pre_digest())
+	}
+
+	fn change_authorities(authorities: Vec<AuthorityId>) -> DispatchResult {
+		ensure!(!authorities.is_empty(), Error::<T>::NoAuthorities);
+		ensure!(
+			authorities.iter().all(|a| a.scheme() == authorities[0].scheme()),
+			Error::<T>::MixedSealSchemes
+		);
+		ensure!(
+			authorities.iter().enumerate().all(|(i, a)| !authorities[..i].contains(a)),
+			Error::<T>::DuplicateAuthority
+		);
+
+		let bounded = BoundedVec::<_, T::MaxAuthorities>::try_from(authorities.clone())
+			.map_err(|_| Error::<T>::TooManyAuthorities)?;
+		let schedule = Self::compute_schedule(&authorities);
+		ensure!(!schedule.is_empty(), Error::<T>::NoWeight);
+
+		Authorities::<T>::put(bounded);
+		Schedule::<T>::put(schedule);
+		FinalityKeysChanged::<T>::put(true);
+		Self::deposit_event(Event::AuthoritiesChanged { authorities });
+		Ok(())
+	}
+
+	/// The schedule of `authorities` under their current weights.
+	fn compute_schedule(
+		authorities: &[AuthorityId],
+	) -> BoundedVec<AuthorityIndex, T::MaxScheduleLength> {
+		let weights: Vec<u64> = authorities.iter().map(AuthorityWeights::<T>::get).collect();
+		let schedule = weighted_schedule(&weights, T::MaxScheduleLength::get());
+		BoundedVec::truncate_from(schedule)
+	}
+
+	fn initialize_authorities(authorities: &[AuthorityId]) {
+		if authorities.is_empty() {
+			return;
+		}
+		assert!(Authorities::<T>::get().is_empty(), "Authorities are already initialized!");
+		assert!(
+			authorities.iter().all(|a| a.scheme() == authorities[0].scheme()),
+			"All authorities must seal with the same scheme"
+		);
+		let bounded = BoundedVec::<_, T::MaxAuthorities>::try_from(authorities.to_vec())
+			.expect("Initial authority set must be less than T::MaxAuthorities");
+		let schedule = Self::compute_schedule(authorities);
+		assert!(!schedule.is_empty(), "At least one authority must have a positive weight");
+		Authorities::<T>::put(bounded);
+		Schedule::<T>::put(schedule);
+	}
+}
+
+impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T> {
+	/// Checks that the slot claimed by a round-robin block is the one its timestamp falls in, so
+	/// that authors cannot back-date blocks to earlier slots.
+	fn on_timestamp_set(moment: T::Moment) {
+		let Some(slot_duration) = SlotDuration::<T>::get() else {
+			return;
+		};
+		// Only round-robin blocks claim a slot
+		let Some(pre_digest) = Self::current_pre_digest() else {
+			return;
+		};
+		let timestamp_slot = moment.saturated_into::<u64>() / slot_duration;
+		assert_eq!(*pre_digest.slot, timestamp_slot, "Timestamp slot must match the claimed slot");
+	}
+}
+
+/// Told the finality keys of the authority set whenever it changes, such as to schedule a change
+/// of the GRANDPA voters.
+pub trait OnAuthoritiesChanged<FinalityId> {
+	/// Called at the end of a block changing the authority set or its finality keys, with the
+	/// finality keys of the new set in authoring order. Authorities without one are left out.
+	///
+	/// The calls changing the set pay for reading the keys, not for this.
+	fn on_authorities_changed(finality_keys: Vec<FinalityId>);
+}
+
+impl<FinalityId> OnAuthoritiesChanged<FinalityId> for () {
+	fn on_authorities_changed(_: Vec<FinalityId>) {}
+}
+
+impl<T: Config> FindAuthor<AuthorityId> for Pallet<T> {
+	fn find_author<'a, I>(digests: I) -> Option<AuthorityId>
+	where
+		I: 'a + IntoIterator<Item = (frame::deps::sp_runtime::ConsensusEngineId, &'a [u8])>,
+	{
+		let pre_digest = digests.into_iter().find_map(|(id, mut data)| {
+			(id == ROUND_ROBIN_ENGINE_ID)
+				.then(|| PreDigest::decode(&mut data).ok())
+				.flatten()
+		})?;
+		Authorities::<T>::get().get(pre_digest.authority_index as usize).cloned()
+	}
+}
+
+/// Session keys are sr25519 only: the keystore cannot generate ML-DSA-65 keys, which are inserted
+/// with the node's `insert-pq-key` command instead. Runtimes should refuse to generate session
+/// keys on chains whose [`Pallet::seal_scheme`] is ML-DSA-65.
+impl<T: Config> frame::deps::sp_runtime::BoundToRuntimeAppPublic for Pallet<T> {
+	type Public = sp_consensus_round_robin::sr25519::AuthorityId;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame::{
+		deps::{
+			sp_core::{Pair, H256},
+			sp_runtime::{traits::Header as _, Digest, DigestItem},
+		},
+		testing_prelude::*,
+	};
+	use sp_consensus_round_robin::{ml_dsa, sr25519::AuthorityPair, AuthoritySignature};
+
+	type Block = MockBlock<Test>;
+
+	construct_runtime!(
+		pub enum Test {
+			System: frame_system,
+			Timestamp: pallet_timestamp,
+			RoundRobin: crate,
+		}
+	);
+
+	#[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+	impl frame_system::Config for Test {
+		type Block = Block;
+	}
+
+	impl pallet_timestamp::Config for Test {
+		type Moment = u64;
+		type OnTimestampSet = RoundRobin;
+		type MinimumPeriod = ConstU64<1>;
+		type WeightInfo = ();
+	}
+
+	impl Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type AuthorityOrigin = EnsureRoot<u64>;
+		type MaxAuthorities = ConstU32<4>;
+		type MaxScheduleLength = ConstU32<16>;
+		type ReportLongevity = ConstU64<10>;
+		type FinalityId = u64;
+		type OnAuthoritiesChanged = ();
+	}
+
+	impl<C> SendTransactionTypes<C> for Test
+	where
+		RuntimeCall: From<C>,
+	{
+		type OverarchingCall = RuntimeCall;
+		type Extrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	}
+
+	fn pair(seed: u8) -> AuthorityPair {
+		AuthorityPair::from_seed(&[seed; 32])
+	}
+
+	fn authority(seed: u8) -> AuthorityId {
+		pair(seed).public().into()
+	}
+
+	/// Authorities 1 and 2 take turns by block height.
+	fn genesis() -> GenesisConfig<Test> {
+		GenesisConfig { authorities: vec![authority(1), authority(2)], ..Default::default() }
+	}
+
+	fn new_test_ext() -> TestState {
+		new_test_ext_with(genesis())
+	}
+
+	fn new_test_ext_with(round_robin: GenesisConfig<Test>) -> TestState {
+		let storage = RuntimeGenesisConfig { round_robin, ..Default::default() }
+			.build_storage()
+			.unwrap();
+		TestState::new(storage)
+	}
+
+	/// Starts block `number`, claiming `slot` for the authority at `authority_index`.
+	fn start_block(number: u64, authority_index: u32, slot: u64) {
+		start_block_skipping(number, authority_index, slot, 0);
+	}
+
+	/// Starts block `number` like [`start_block`], in place of the `skipped` authorities before
+	/// `authority_index` that missed their turn.
+	fn start_block_skipping(number: u64, authority_index: u32, slot: u64, skipped: u32) {
+		let pre_digest = PreDigest { authority_index, slot: slot.into(), skipped };
+		System::initialize(
+			&number,
+			&[number as u8; 32].into(),
+			&Digest { logs: vec![DigestItem::round_robin_pre_digest(pre_digest)] },
+		);
+		RoundRobin::on_initialize(number);
+	}
+
+	/// Sets the timestamp of block `number` and finalizes it.
+	fn finish_block(number: u64, timestamp: u64) {
+		Timestamp::set_timestamp(timestamp);
+		RoundRobin::on_finalize(number);
+	}
+
+	/// A header claiming `slot`, sealed by authority `seed`. Headers of different `state_root`s
+	/// differ.
+	fn sealed_header(seed: u8, slot: u64, state_root: u8) -> HeaderFor<Test> {
+		sealed_child(seed, slot, state_root, H256::zero())
+	}
+
+	/// A header like [`sealed_header`] on the parent `parent_hash`.
+	fn sealed_child(seed: u8, slot: u64, state_root: u8, parent_hash: H256) -> HeaderFor<Test> {
+		let mut header = HeaderFor::<Test>::new(
+			1,
+			H256::zero(),
+			H256::repeat_byte(state_root),
+			parent_hash,
+			Default::default(),
+		);
+		let pre_digest = PreDigest { authority_index: 0, slot: slot.into(), skipped: 0 };
+		header.digest_mut().push(DigestItem::round_robin_pre_digest(pre_digest));
+		let signature = pair(seed).sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(DigestItem::round_robin_seal(AuthoritySignature::Sr25519(signature)));
+		header
+	}
+
+	/// A proof of authority `seed` sealing two blocks for `slot`.
+	fn equivocation(seed: u8, slot: u64) -> Box<EquivocationProof<HeaderFor<Test>>> {
+		Box::new(EquivocationProof {
+			offender: authority(seed),
+			slot: slot.into(),
+			first_header: sealed_header(seed, slot, 1),
+			second_header: sealed_header(seed, slot, 2),
+		})
+	}
+
+	fn validate(
+		equivocation_proof: Box<EquivocationProof<HeaderFor<Test>>>,
+	) -> TransactionValidity {
+		let call = Call::report_equivocation { equivocation_proof };
+		RoundRobin::validate_unsigned(TransactionSource::Local, &call)
+	}
+
+	fn block_author() -> Option<AuthorityId> {
+		let digest = System::digest();
+		RoundRobin::find_author(digest.logs().iter().filter_map(|item| item.as_pre_runtime()))
+	}
+
+	#[test]
+	fn only_the_authority_origin_sets_authorities() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				RoundRobin::set_authorities(RuntimeOrigin::signed(1), vec![authority(3)]),
+				DispatchError::BadOrigin
+			);
+			assert_noop!(
+				RoundRobin::add_authority(RuntimeOrigin::signed(1), authority(3)),
+				DispatchError::BadOrigin
+			);
+			assert_noop!(
+				RoundRobin::remove_authority(RuntimeOrigin::signed(1), authority(1)),
+				DispatchError::BadOrigin
+			);
+
+			assert_ok!(RoundRobin::set_authorities(RuntimeOrigin::root(), vec![authority(3)]));
+			assert_eq!(RoundRobin::authorities(), vec![authority(3)]);
+		});
+	}
+
+	#[test]
+	fn rejects_empty_duplicate_and_oversized_sets() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				RoundRobin::set_authorities(RuntimeOrigin::root(), vec![]),
+				Error::<Test>::NoAuthorities
+			);
+			assert_noop!(
+				RoundRobin::set_authorities(
+					RuntimeOrigin::root(),
+					vec![authority(3), authority(4), authority(3)]
+				),
+				Error::<Test>::DuplicateAuthority
+			);
+			assert_noop!(
+				RoundRobin::add_authority(RuntimeOrigin::root(), authority(1)),
+				Error::<Test>::DuplicateAuthority
+			);
+			assert_noop!(
+				RoundRobin::set_authorities(
+					RuntimeOrigin::root(),
+					(1..=5).map(authority).collect()
+				),
+				Error::<Test>::TooManyAuthorities
+			);
+			assert_noop!(
+				RoundRobin::remove_authority(RuntimeOrigin::root(), authority(3)),
+				Error::<Test>::NotAnAuthority
+			);
+			assert_ok!(RoundRobin::remove_authority(RuntimeOrigin::root(), authority(1)));
+			assert_noop!(
+				RoundRobin::remove_authority(RuntimeOrigin::root(), authority(2)),
+				Error::<Test>::NoAuthorities
+			);
+		});
+	}
+
+	#[test]
+	fn seal_scheme_follows_the_authorities() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(RoundRobin::seal_scheme(), Some(SealScheme::Sr25519));
+			let ml_dsa = ml_dsa::AuthorityPair::from_seed(&[1; 32]).public();
+			assert_ok!(RoundRobin::set_authorities(
+				RuntimeOrigin::root(),
+				vec![AuthorityId::MlDsa65(Box::new(ml_dsa))]
+			));
+			assert_eq!(RoundRobin::seal_scheme(), Some(SealScheme::MlDsa65));
+		});
+		new_test_ext_with(GenesisConfig::default())
+			.execute_with(|| assert_eq!(RoundRobin::seal_scheme(), None));
+	}
+
+	#[test]
+	fn authority_changes_take_effect_from_the_next_block() {
+		new_test_ext().execute_with(|| {
+			start_block(1, 0, 1);
+			assert_eq!(block_author(), Some(authority(1)));
+
+			assert_ok!(RoundRobin::set_authorities(
+				RuntimeOrigin::root(),
+				vec![authority(3), authority(4)]
+			));
+			System::assert_last_event(
+				Event::AuthoritiesChanged { authorities: vec![authority(3), authority(4)] }.into(),
+			);
+
+			// Block #2 is checked against the set its parent left behind
+			start_block(2, 0, 2);
+			assert_eq!(block_author(), Some(authority(3)));
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Slot must increase")]
+	fn blocks_must_claim_later_slots() {
+		new_test_ext().execute_with(|| {
+			start_block(1, 0, 5);
+			start_block(2, 1, 5);
+		});
+	}
+
+	#[test]
+	fn fallback_authors_wait_for_the_missed_turn_timeout() {
+		let genesis = GenesisConfig { missed_turn_timeout: Some(1_000), ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			start_block(1, 0, 1);
+			finish_block(1, 6_000);
+
+			// Authority 1 missed its turn at block #2, so authority 0 authors after the timeout
+			start_block_skipping(2, 0, 2, 1);
+			assert_eq!(FallbackDeadline::<Test>::get(), Some(7_000));
+			finish_block(2, 7_000);
+			assert_eq!(FallbackDeadline::<Test>::get(), None);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Missed-turn timeout has not elapsed")]
+	fn fallback_authors_before_the_timeout_are_rejected() {
+		let genesis = GenesisConfig { missed_turn_timeout: Some(1_000), ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			start_block(1, 0, 1);
+			finish_block(1, 6_000);
+
+			start_block_skipping(2, 0, 2, 1);
+			finish_block(2, 6_999);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Missed turns are never skipped on this chain")]
+	fn missed_turns_are_not_skipped_without_a_timeout() {
+		new_test_ext().execute_with(|| {
+			start_block(1, 0, 1);
+			finish_block(1, 6_000);
+
+			start_block_skipping(2, 0, 2, 1);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Missed-turn timeout must exceed the block time")]
+	fn missed_turn_timeout_must_exceed_the_block_time() {
+		let genesis = GenesisConfig {
+			missed_turn_timeout: Some(1_000),
+			block_time: Some(1_000),
+			..genesis()
+		};
+		new_test_ext_with(genesis);
+	}
+
+	#[test]
+	fn blocks_claim_the_slot_of_their_timestamp() {
+		let genesis = GenesisConfig { slot_duration: Some(1_000), ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			start_block(1, 1, 5);
+			finish_block(1, 5_999);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Timestamp slot must match the claimed slot")]
+	fn blocks_cannot_be_back_dated_to_earlier_slots() {
+		let genesis = GenesisConfig { slot_duration: Some(1_000), ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			start_block(1, 1, 5);
+			finish_block(1, 6_000);
+		});
+	}
+
+	#[test]
+	fn minimum_period_is_half_the_slot_duration_or_block_time() {
+		new_test_ext().execute_with(|| {
+			start_block(1, 0, 1);
+			assert_eq!(RoundRobin::minimum_period(), 1);
+		});
+		let slots = GenesisConfig { slot_duration: Some(6_000), ..genesis() };
+		new_test_ext_with(slots).execute_with(|| {
+			start_block(1, 0, 1);
+			assert_eq!(RoundRobin::minimum_period(), 3_000);
+		});
+		let heights = GenesisConfig { block_time: Some(1_000), ..genesis() };
+		new_test_ext_with(heights).execute_with(|| {
+			start_block(1, 0, 1);
+			assert_eq!(RoundRobin::minimum_period(), 500);
+		});
+	}
+
+	#[test]
+	fn minimum_period_is_one_millisecond_without_round_robin_blocks() {
+		let heights = GenesisConfig { block_time: Some(1_000), ..genesis() };
+		new_test_ext_with(heights).execute_with(|| {
+			assert_eq!(RoundRobin::minimum_period(), 1);
+			// Blocks of another engine carry no round-robin pre-digest
+			System::initialize(&1, &[1; 32].into(), &Default::default());
+			RoundRobin::on_initialize(1);
+			assert_eq!(RoundRobin::minimum_period(), 1);
+		});
+	}
+
+	#[test]
+	fn weights_of_zero_are_rejected_for_the_whole_set() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				RoundRobin::set_weight(RuntimeOrigin::signed(1), authority(1), 0),
+				DispatchError::BadOrigin
+			);
+
+			assert_ok!(RoundRobin::set_weight(RuntimeOrigin::root(), authority(1), 0));
+			assert_eq!(Schedule::<Test>::get(), vec![1]);
+			assert_noop!(
+				RoundRobin::set_weight(RuntimeOrigin::root(), authority(2), 0),
+				Error::<Test>::NoWeight
+			);
+			assert_noop!(
+				RoundRobin::set_authorities(RuntimeOrigin::root(), vec![authority(1)]),
+				Error::<Test>::NoWeight
+			);
+		});
+	}
+
+	#[test]
+	fn weights_outside_the_set_apply_once_it_joins() {
+		new_test_ext().execute_with(|| {
+			System::set_block_number(1);
+			assert_ok!(RoundRobin::set_weight(RuntimeOrigin::root(), authority(3), 2));
+			System::assert_last_event(
+				Event::WeightChanged { authority: authority(3), weight: 2 }.into(),
+			);
+			assert_eq!(Schedule::<Test>::get(), vec![0, 1]);
+
+			assert_ok!(RoundRobin::add_authority(RuntimeOrigin::root(), authority(3)));
+			assert_eq!(Schedule::<Test>::get(), weighted_schedule(&[1, 1, 2], 16));
+
+			// The schedule only names members of the set, whatever the weights left behind
+			assert_ok!(RoundRobin::remove_authority(RuntimeOrigin::root(), authority(1)));
+			assert_eq!(Schedule::<Test>::get(), weighted_schedule(&[1, 2], 16));
+		});
+	}
+
+	#[test]
+	fn the_stored_schedule_follows_the_weights() {
+		let genesis = GenesisConfig { weights: vec![(authority(1), 3)], ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			assert_eq!(Schedule::<Test>::get(), weighted_schedule(&[3, 1], 16));
+
+			// Weights too large for the schedule are scaled down to fit
+			assert_ok!(RoundRobin::set_weight(RuntimeOrigin::root(), authority(2), 1_000));
+			let schedule = Schedule::<Test>::get();
+			assert_eq!(schedule, weighted_schedule(&[3, 1_000], 16));
+			assert!(schedule.len() <= 16);
+			assert!(schedule.contains(&0));
+		});
+	}
+
+	#[test]
+	fn the_schedule_is_reshuffled_at_epoch_boundaries_only() {
+		let genesis = GenesisConfig {
+			authorities: (1..=4).map(authority).collect(),
+			epoch_length: Some(4),
+			..Default::default()
+		};
+		new_test_ext_with(genesis).execute_with(|| {
+			// Epochs 0 and 1 share the genesis seed, so the first reshuffle is into epoch 2
+			let mut epochs = Vec::<Vec<AuthorityIndex>>::new();
+			let mut announced = None;
+			for slot in 4..20 {
+				start_block(slot, 0, slot);
+				let schedule = RoundRobin::schedule(slot.into());
+				let mut turns = schedule.clone();
+				turns.sort();
+				assert_eq!(turns, [0, 1, 2, 3]);
+
+				if slot % 4 == 0 {
+					if let Some(previous) = epochs.last() {
+						assert_ne!(&schedule, previous, "no reshuffle at slot {slot}");
+					}
+					if let Some(announced) = announced.take() {
+						assert_eq!(schedule, announced, "epoch {} changed its leaders", slot / 4);
+					}
+					epochs.push(schedule);
+				} else {
+					assert_eq!(Some(&schedule), epochs.last(), "reshuffled at slot {slot}");
+				}
+
+				// The leaders of the next epoch are known by the end of this one
+				if slot % 4 == 3 {
+					announced = Some(RoundRobin::schedule((slot + 1).into()));
+				}
+			}
+			assert_eq!(epochs.len(), 4);
+		});
+	}
+
+	#[test]
+	fn reported_equivocators_are_removed() {
+		let genesis = GenesisConfig { authorities: (1..=3).map(authority).collect(), ..genesis() };
+		new_test_ext_with(genesis).execute_with(|| {
+			System::set_block_number(1);
+			assert_noop!(
+				RoundRobin::report_equivocation(RuntimeOrigin::signed(1), equivocation(2, 3)),
+				DispatchError::BadOrigin
+			);
+			let report = Call::report_equivocation { equivocation_proof: equivocation(2, 3) };
+			assert_eq!(
+				RoundRobin::validate_unsigned(TransactionSource::External, &report),
+				InvalidTransaction::Call.into()
+			);
+			assert_eq!(validate(equivocation(2, 3)).map(|valid| valid.longevity), Ok(10));
+
+			assert_ok!(RoundRobin::report_equivocation(RuntimeOrigin::none(), equivocation(2, 3)));
+			assert_eq!(RoundRobin::authorities(), [authority(1), authority(3)]);
+			System::assert_last_event(
+				Event::EquivocationReported { offender: authority(2), slot: 3.into() }.into(),
+			);
+		});
+	}
+
+	#[test]
+	fn invalid_and_duplicate_reports_are_rejected() {
+		new_test_ext().execute_with(|| {
+			let report = |proof| RoundRobin::report_equivocation(RuntimeOrigin::none(), proof);
+
+			let mut same_block = equivocation(1, 3);
+			same_block.second_header = same_block.first_header.clone();
+			let mut other_sealer = equivocation(1, 3);
+			other_sealer.second_header = sealed_header(2, 3, 2);
+			let mut other_slot = equivocation(1, 3);
+			other_slot.second_header = sealed_header(1, 4, 2);
+			for proof in [same_block, other_sealer, other_slot] {
+				assert_eq!(validate(proof.clone()), InvalidTransaction::BadProof.into());
+				assert_noop!(report(proof), Error::<Test>::InvalidEquivocationProof);
+			}
+			assert_noop!(report(equivocation(3, 3)), Error::<Test>::NotAnAuthority);
+
+			assert_ok!(report(equivocation(1, 3)));
+			assert_eq!(validate(equivocation(1, 3)), InvalidTransaction::BadProof.into());
+			assert_noop!(report(equivocation(1, 3)), Error::<Test>::DuplicateOffenceReport);
+
+			// Removing the last authority would halt the chain
+			assert_noop!(report(equivocation(2, 3)), Error::<Test>::NoAuthorities);
+		});
+	}
+
+	#[test]
+	fn reauthoring_a_height_on_another_parent_is_no_equivocation() {
+		let reauthored = || {
+			let mut proof = equivocation(1, 3);
+			proof.second_header = sealed_child(1, 3, 2, H256::repeat_byte(1));
+			proof
+		};
+		new_test_ext().execute_with(|| {
+			assert_eq!(validate(reauthored()), InvalidTransaction::BadProof.into());
+			assert_noop!(
+				RoundRobin::report_equivocation(RuntimeOrigin::none(), reauthored()),
+				Error::<Test>::InvalidEquivocationProof
+			);
+		});
+
+		// Slots are only claimed once whatever the parent
+		let slots = GenesisConfig { slot_duration: Some(1_000), ..genesis() };
+		new_test_ext_with(slots).execute_with(|| {
+			assert_ok!(RoundRobin::report_equivocation(RuntimeOrigin::none(), reauthored()));
+		});
+	}
+
+	#[test]
+	fn reported_offences_are_bounded_until_they_expire() {
+		new_test_ext().execute_with(|| {
+			let report = |proof| RoundRobin::report_equivocation(RuntimeOrigin::none(), proof);
+
+			// Authority 1 rejoins after every report
+			for slot in 1..=4 {
+				assert_ok!(report(equivocation(1, slot)));
+				assert_ok!(RoundRobin::add_authority(RuntimeOrigin::root(), authority(1)));
+			}
+			assert_noop!(report(equivocation(1, 5)), Error::<Test>::TooManyOffenceReports);
+
+			// Slots 1 to 4 are more than `ReportLongevity` slots old at slot 15
+			start_block(1, 0, 15);
+			assert_noop!(report(equivocation(1, 4)), Error::<Test>::StaleOffenceReport);
+			assert_ok!(report(equivocation(1, 5)));
+			assert_eq!(ReportedOffences::<Test>::get().into_inner(), [(authority(1), 5.into())]);
+		});
+	}
+}
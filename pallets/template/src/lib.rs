@@ -12,6 +12,7 @@ pub use pallet::*;
 
 #[frame::pallet]
 pub mod pallet {
+	#[allow(unused_imports)]
 	use super::*;
 
 	#[pallet::config]
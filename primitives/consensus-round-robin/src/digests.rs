@@ -0,0 +1,38 @@
+//! Round-robin digest items: the pre-runtime digest naming the author and the seal.
+
+use crate::{AuthoritySignature, PreDigest, ROUND_ROBIN_ENGINE_ID};
+use codec::Encode;
+use polkadot_sdk::sp_runtime::generic::DigestItem;
+
+/// A digest item which is usable with round-robin consensus.
+pub trait CompatibleDigestItem: Sized {
+	/// Construct a digest item which contains a signature on the hash.
+	fn round_robin_seal(signature: AuthoritySignature) -> Self;
+
+	/// If this item is a round-robin seal, return the signature.
+	fn as_round_robin_seal(&self) -> Option<AuthoritySignature>;
+
+	/// Construct a digest item which contains the round-robin pre-digest.
+	fn round_robin_pre_digest(pre_digest: PreDigest) -> Self;
+
+	/// If this item is a round-robin pre-digest, return it.
+	fn as_round_robin_pre_digest(&self) -> Option<PreDigest>;
+}
+
+impl CompatibleDigestItem for DigestItem {
+	fn round_robin_seal(signature: AuthoritySignature) -> Self {
+		DigestItem::Seal(ROUND_ROBIN_ENGINE_ID, signature.encode())
+	}
+
+	fn as_round_robin_seal(&self) -> Option<AuthoritySignature> {
+		self.seal_try_to(&ROUND_ROBIN_ENGINE_ID)
+	}
+
+	fn round_robin_pre_digest(pre_digest: PreDigest) -> Self {
+		DigestItem::PreRuntime(ROUND_ROBIN_ENGINE_ID, pre_digest.encode())
+	}
+
+	fn as_round_robin_pre_digest(&self) -> Option<PreDigest> {
+		self.pre_runtime_try_to(&ROUND_ROBIN_ENGINE_ID)
+	}
+}
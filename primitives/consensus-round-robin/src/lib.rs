@@ -0,0 +1,570 @@
+//! Primitives for round-robin block authoring.
+//!
+//! Authorities take turns producing blocks in the order they are stored on chain. Every block
+//! carries a pre-runtime digest naming its author and slot, and a seal signed with the author's
+//! key.
+//!
+//! Seals are signed either with sr25519 or, for quantum resistance, with ML-DSA-65. The scheme is
+//! chosen per chain by the kind of authority keys in its genesis.
+//!
+//! Chains without a slot duration give turns by height: the slot of a block is its number. Chains
+//! with a slot duration derive the slot from wall-clock time, so a validator that misses its slot
+//! is simply skipped by the next one.
+//!
+//! Turns follow a schedule repeating the authority indices in proportion to their on-chain weights,
+//! which is the plain rotation while all weights are equal. Chains may shuffle the schedule every
+//! epoch with on-chain randomness.
+//!
+//! Chains giving turns by height may set a missed-turn timeout instead. Once it has passed since
+//! the parent's timestamp, the validator after the scheduled one may author in its place, marking
+//! the skip in the pre-digest; after twice the timeout the one after that, and so on.
+//!
+//! An authority sealing two different blocks for the same slot equivocates. Nodes importing both
+//! report them to the runtime as an [`EquivocationProof`], which removes the offender from the
+//! authority set.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use codec::{Decode, Encode, MaxEncodedLen};
+use polkadot_sdk::{
+	sp_application_crypto::RuntimeAppPublic,
+	sp_core::{crypto::KeyTypeId, hashing::blake2_256},
+	sp_runtime::{
+		traits::{Block as BlockT, Header as HeaderT, IdentifyAccount},
+		AccountId32, ConsensusEngineId,
+	},
+	*,
+};
+use scale_info::TypeInfo;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sp_pq_crypto::MultiSigner;
+
+pub mod digests;
+
+pub use digests::CompatibleDigestItem;
+pub use sp_consensus_slots::{Slot, SlotDuration};
+
+/// The `ConsensusEngineId` of round-robin authoring.
+pub const ROUND_ROBIN_ENGINE_ID: ConsensusEngineId = *b"rrbn";
+
+/// The key type of sr25519 round-robin authority keys.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"rrbn");
+
+/// Round-robin authorities sealing with sr25519.
+pub mod sr25519 {
+	mod app_sr25519 {
+		use polkadot_sdk::sp_application_crypto::{app_crypto, sr25519};
+		app_crypto!(sr25519, crate::KEY_TYPE);
+	}
+
+	/// An sr25519 round-robin authority identifier.
+	pub type AuthorityId = app_sr25519::Public;
+
+	/// An sr25519 round-robin seal signature.
+	pub type AuthoritySignature = app_sr25519::Signature;
+
+	/// An sr25519 round-robin authority keypair.
+	#[cfg(feature = "std")]
+	pub type AuthorityPair = app_sr25519::Pair;
+}
+
+/// Round-robin authorities sealing with the post-quantum ML-DSA-65 scheme.
+pub mod ml_dsa {
+	use super::KeyTypeId;
+
+	/// The key type of ML-DSA-65 round-robin authority keys.
+	///
+	/// These keys are not held by the node's regular keystore, which only supports classical
+	/// schemes.
+	pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"rrpq");
+
+	pub use sp_pq_crypto::ml_dsa::{Public as AuthorityId, Signature as AuthoritySignature};
+
+	#[cfg(feature = "std")]
+	pub use sp_pq_crypto::ml_dsa::Pair as AuthorityPair;
+}
+
+/// The signature scheme used to seal blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SealScheme {
+	/// Classical sr25519 signatures.
+	Sr25519,
+	/// Post-quantum ML-DSA-65 signatures.
+	MlDsa65,
+}
+
+/// A round-robin authority identifier.
+///
+/// ML-DSA-65 keys are boxed, so sets of mostly sr25519 authorities stay small; boxing does not
+/// change the encoding.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AuthorityId {
+	/// An authority sealing with sr25519.
+	Sr25519(sr25519::AuthorityId),
+	/// An authority sealing with ML-DSA-65.
+	MlDsa65(Box<ml_dsa::AuthorityId>),
+}
+
+/// The signature of a round-robin authority over a block's pre-seal header hash.
+///
+/// ML-DSA-65 signatures are boxed, as in [`AuthorityId`].
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum AuthoritySignature {
+	/// An sr25519 signature.
+	Sr25519(sr25519::AuthoritySignature),
+	/// An ML-DSA-65 signature.
+	MlDsa65(Box<ml_dsa::AuthoritySignature>),
+}
+
+impl AuthorityId {
+	/// The scheme this authority seals blocks with.
+	pub fn scheme(&self) -> SealScheme {
+		match self {
+			AuthorityId::Sr25519(_) => SealScheme::Sr25519,
+			AuthorityId::MlDsa65(_) => SealScheme::MlDsa65,
+		}
+	}
+
+	/// Verifies `signature` over `message`. Signatures of a different scheme are rejected.
+	pub fn verify(&self, message: &[u8], signature: &AuthoritySignature) -> bool {
+		match (self, signature) {
+			(AuthorityId::Sr25519(public), AuthoritySignature::Sr25519(signature)) =>
+				public.verify(&message, signature),
+			(AuthorityId::MlDsa65(public), AuthoritySignature::MlDsa65(signature)) =>
+				public.verify(message, signature),
+			_ => false,
+		}
+	}
+}
+
+impl IdentifyAccount for AuthorityId {
+	type AccountId = AccountId32;
+
+	/// The account of an authority is the one its key controls when signing transactions.
+	fn into_account(self) -> AccountId32 {
+		match self {
+			AuthorityId::Sr25519(public) => MultiSigner::Sr25519(public.into()).into_account(),
+			AuthorityId::MlDsa65(public) => MultiSigner::MlDsa65(public).into_account(),
+		}
+	}
+}
+
+impl From<sr25519::AuthorityId> for AuthorityId {
+	fn from(public: sr25519::AuthorityId) -> Self {
+		AuthorityId::Sr25519(public)
+	}
+}
+
+impl From<ml_dsa::AuthorityId> for AuthorityId {
+	fn from(public: ml_dsa::AuthorityId) -> Self {
+		AuthorityId::MlDsa65(Box::new(public))
+	}
+}
+
+/// The index of an authority in the on-chain authority set.
+pub type AuthorityIndex = u32;
+
+/// Two different headers sealed by the same authority for the same slot.
+pub type EquivocationProof<Header> = sp_consensus_slots::EquivocationProof<Header, AuthorityId>;
+
+/// Pre-runtime digest identifying the author of a block.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PreDigest {
+	/// Index of the author in the authority set of the parent block.
+	pub authority_index: AuthorityIndex,
+	/// The slot the block was authored in.
+	pub slot: Slot,
+	/// The number of authorities skipped because they missed their turn: zero unless the block
+	/// was authored in place of the scheduled author.
+	pub skipped: u32,
+}
+
+/// Returns the index of the authority scheduled to author in `slot` by `schedule`, or `None` if the
+/// schedule is empty.
+pub fn scheduled_author(slot: Slot, schedule: &[AuthorityIndex]) -> Option<AuthorityIndex> {
+	if schedule.is_empty() {
+		return None;
+	}
+	Some(schedule[(*slot % schedule.len() as u64) as usize])
+}
+
+/// Returns the indices of the authorities with turns in `schedule`, i.e. those of positive weight,
+/// in ascending order. Turns missed by the scheduled author pass to the next of them.
+pub fn fallback_order(schedule: &[AuthorityIndex]) -> Vec<AuthorityIndex> {
+	let mut order = schedule.to_vec();
+	order.sort_unstable();
+	order.dedup();
+	order
+}
+
+/// Returns the index of the authority allowed to author in `slot` once the `skipped` authorities
+/// in [`fallback_order`] from the scheduled one missed their turns, or `None` if the schedule is
+/// empty.
+pub fn fallback_author(
+	slot: Slot,
+	skipped: u32,
+	schedule: &[AuthorityIndex],
+) -> Option<AuthorityIndex> {
+	let scheduled = scheduled_author(slot, schedule)?;
+	let order = fallback_order(schedule);
+	let position = order.binary_search(&scheduled).ok()?;
+	Some(order[(position + skipped as usize % order.len()) % order.len()])
+}
+
+/// Returns how many authorities must miss their turns in `slot` before `authority` may author in
+/// their place, zero if it is scheduled, or `None` if it has no turns in `schedule`.
+///
+/// The inverse of [`fallback_author`].
+pub fn fallback_position(
+	slot: Slot,
+	authority: AuthorityIndex,
+	schedule: &[AuthorityIndex],
+) -> Option<u32> {
+	let scheduled = scheduled_author(slot, schedule)?;
+	let order = fallback_order(schedule);
+	let position = order.binary_search(&scheduled).ok()?;
+	let own_position = order.binary_search(&authority).ok()?;
+	Some(((own_position + order.len() - position) % order.len()) as u32)
+}
+
+/// Computes the authoring schedule of authorities with the given `weights`: a sequence of authority
+/// indices, repeated slot after slot, in which each authority appears in proportion to its weight.
+///
+/// Turns are spread as evenly as possible (smooth weighted round-robin), so equal weights give the
+/// plain rotation `0, 1, 2, ...`. Authorities of weight zero never author. Weights are scaled down
+/// so the schedule is at most `max_len` long, keeping every positive weight at least one turn;
+/// `max_len` must exceed the number of authorities.
+pub fn weighted_schedule(weights: &[u64], max_len: u32) -> Vec<AuthorityIndex> {
+	let total: u128 = weights.iter().map(|w| *w as u128).sum();
+	let max_len = max_len as u128;
+	let weights: Vec<u128> = if total <= max_len {
+		weights.iter().map(|w| *w as u128).collect()
+	} else {
+		// Leaves room for rounding every positive weight up to one turn
+		let room = max_len.saturating_sub(weights.len() as u128);
+		weights
+			.iter()
+			.map(|w| if *w == 0 { 0 } else { *w as u128 * room / total + 1 })
+			.collect()
+	};
+	let total: u128 = weights.iter().sum();
+
+	let mut current = alloc::vec![0i128; weights.len()];
+	let mut schedule = Vec::with_capacity(total as usize);
+	for _ in 0..total {
+		let mut best = 0;
+		for (i, weight) in weights.iter().enumerate() {
+			current[i] += *weight as i128;
+			if current[i] > current[best] {
+				best = i;
+			}
+		}
+		current[best] -= total as i128;
+		schedule.push(best as AuthorityIndex);
+	}
+	schedule
+}
+
+/// Shuffles `schedule` deterministically from `seed` (Fisher-Yates, drawing from hashes of the
+/// seed).
+pub fn shuffle_schedule(schedule: &mut [AuthorityIndex], seed: &[u8; 32]) {
+	for i in (1..schedule.len()).rev() {
+		let draw = blake2_256(&(seed, i as u64).encode());
+		let draw = u64::from_le_bytes(draw[..8].try_into().expect("hash is 32 bytes; qed"));
+		schedule.swap(i, (draw % (i as u64 + 1)) as usize);
+	}
+}
+
+/// Extracts the round-robin pre-digest from a header, if there is exactly one.
+pub fn find_pre_digest<Header: HeaderT>(header: &Header) -> Option<PreDigest> {
+	let mut pre_digest = None;
+	for log in header.digest().logs() {
+		match (log.as_round_robin_pre_digest(), pre_digest.is_some()) {
+			(Some(_), true) => return None,
+			(Some(digest), false) => pre_digest = Some(digest),
+			(None, _) => {},
+		}
+	}
+	pre_digest
+}
+
+/// Strips the seal from a copy of `header` and checks it was signed by `author`.
+///
+/// Returns the pre-digest of the header if the seal is valid.
+pub fn check_seal<Header: HeaderT>(header: &Header, author: &AuthorityId) -> Option<PreDigest> {
+	let mut header = header.clone();
+	let signature = header.digest_mut().pop()?.as_round_robin_seal()?;
+	let pre_digest = find_pre_digest(&header)?;
+	author.verify(header.hash().as_ref(), &signature).then_some(pre_digest)
+}
+
+/// Checks that `proof` holds two different headers sealed by its offender for its slot.
+///
+/// When turns are given by height, both headers must also share a parent: an authority may author
+/// at the same height again on another fork, after a reorganisation.
+pub fn check_equivocation_proof<Header: HeaderT>(
+	proof: &EquivocationProof<Header>,
+	turns_by_height: bool,
+) -> bool {
+	let first = check_seal(&proof.first_header, &proof.offender);
+	let second = check_seal(&proof.second_header, &proof.offender);
+	match (first, second) {
+		(Some(first), Some(second)) =>
+			first.slot == proof.slot &&
+				second.slot == proof.slot &&
+				proof.first_header.hash() != proof.second_header.hash() &&
+				(!turns_by_height ||
+					proof.first_header.parent_hash() == proof.second_header.parent_hash()),
+		_ => false,
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// API necessary for round-robin block authoring and verification.
+	pub trait RoundRobinApi {
+		/// Returns the ordered round-robin authority set.
+		fn authorities() -> Vec<AuthorityId>;
+
+		/// Returns the slot duration, or `None` if turns are given by block height.
+		fn slot_duration() -> Option<SlotDuration>;
+
+		/// Returns the authoring schedule for a block in `slot` built on this one: the authority
+		/// indices taking turns, slot after slot.
+		fn schedule(slot: Slot) -> Vec<AuthorityIndex>;
+
+		/// Returns the earliest timestamp, in milliseconds, at which a block skipping `skipped`
+		/// authorities that missed their turn may be built on this block, or `None` if missed
+		/// turns are never skipped.
+		fn fallback_timestamp(skipped: u32) -> Option<u64>;
+
+		/// Returns how long, in milliseconds, validators wait after a block before building on
+		/// it, or `None` if only the nodes know. Only set when turns are given by height.
+		fn block_time() -> Option<u64>;
+
+		/// Returns how long, in milliseconds, after a block's timestamp the validator after the
+		/// scheduled one may author in its place, or `None` if missed turns are never skipped.
+		fn missed_turn_timeout() -> Option<u64>;
+
+		/// Returns the timestamp, in milliseconds, that the inherents among `extrinsics`, the body
+		/// of a block built on this one, set.
+		// A slice does not decode, and taking the body by value would make callers clone it
+		#[allow(clippy::ptr_arg)]
+		fn block_timestamp(extrinsics: &Vec<<Block as BlockT>::Extrinsic>) -> Option<u64>;
+
+		/// Submits an unsigned extrinsic reporting `equivocation_proof` to the transaction pool.
+		/// Only useful in an offchain context, with the transaction pool extension registered.
+		///
+		/// Returns `None` if the extrinsic could not be submitted.
+		fn submit_report_equivocation_unsigned_extrinsic(
+			equivocation_proof: EquivocationProof<<Block as BlockT>::Header>,
+		) -> Option<()>;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn equal_weights_give_the_plain_rotation() {
+		assert_eq!(weighted_schedule(&[1, 1, 1], 1024), [0, 1, 2]);
+		assert_eq!(weighted_schedule(&[5, 5], 1024), [0, 1, 0, 1, 0, 1, 0, 1, 0, 1]);
+	}
+
+	#[test]
+	fn weighted_turns_are_spread_evenly() {
+		assert_eq!(weighted_schedule(&[2, 1, 1], 1024), [0, 1, 2, 0]);
+		assert_eq!(weighted_schedule(&[3, 1], 1024), [0, 0, 1, 0]);
+	}
+
+	#[test]
+	fn weight_zero_authorities_get_no_turns() {
+		assert_eq!(weighted_schedule(&[1, 0, 1], 1024), [0, 2]);
+		assert!(weighted_schedule(&[0, 0], 1024).is_empty());
+		assert!(weighted_schedule(&[], 1024).is_empty());
+	}
+
+	#[test]
+	fn large_weights_are_scaled_down_keeping_every_authority() {
+		let schedule = weighted_schedule(&[1_000_000, 1, 0, 3_000_000], 16);
+		assert!(schedule.len() <= 16, "schedule of {} turns", schedule.len());
+		let turns = |index| schedule.iter().filter(|turn| **turn == index).count();
+		assert_eq!(turns(1), 1);
+		assert_eq!(turns(2), 0);
+		assert!(turns(3) > 2 * turns(0), "{} turns against {}", turns(3), turns(0));
+	}
+
+	#[test]
+	fn shuffle_is_a_deterministic_permutation() {
+		let schedule = weighted_schedule(&[2, 1, 1, 3], 1024);
+		let mut shuffled = schedule.clone();
+		shuffle_schedule(&mut shuffled, &[7; 32]);
+
+		let mut again = schedule.clone();
+		shuffle_schedule(&mut again, &[7; 32]);
+		assert_eq!(shuffled, again);
+
+		let mut other = schedule.clone();
+		shuffle_schedule(&mut other, &[8; 32]);
+		assert_ne!(shuffled, other, "the seed does not change the order");
+
+		let (mut sorted, mut sorted_shuffled) = (schedule, shuffled);
+		sorted.sort();
+		sorted_shuffled.sort();
+		assert_eq!(sorted, sorted_shuffled);
+	}
+
+	#[test]
+	fn missed_turns_pass_to_the_next_authorities() {
+		let schedule = [0, 1, 2];
+		assert_eq!(fallback_author(Slot::from(4), 0, &schedule), Some(1));
+		assert_eq!(fallback_author(Slot::from(4), 1, &schedule), Some(2));
+		assert_eq!(fallback_author(Slot::from(4), 2, &schedule), Some(0));
+		assert_eq!(fallback_author(Slot::from(4), 3, &schedule), Some(1));
+		assert_eq!(fallback_author(Slot::from(4), 0, &[]), None);
+	}
+
+	#[test]
+	fn missed_turns_skip_weight_zero_authorities() {
+		// Authority 1 has weight zero
+		let schedule = weighted_schedule(&[1, 0, 1, 1], 1024);
+		assert_eq!(schedule, [0, 2, 3]);
+		assert_eq!(fallback_order(&schedule), [0, 2, 3]);
+
+		// Slot 3 is authority 0's
+		assert_eq!(fallback_author(Slot::from(3), 1, &schedule), Some(2));
+		assert_eq!(fallback_author(Slot::from(3), 2, &schedule), Some(3));
+		assert_eq!(fallback_author(Slot::from(3), 3, &schedule), Some(0));
+		for skipped in 0..6 {
+			assert_ne!(fallback_author(Slot::from(3), skipped, &schedule), Some(1));
+		}
+	}
+
+	#[test]
+	fn fallback_position_inverts_fallback_author() {
+		let schedule = weighted_schedule(&[2, 0, 1, 1], 1024);
+		for slot in 0..8 {
+			let slot = Slot::from(slot);
+			for skipped in 0..3 {
+				let author = fallback_author(slot, skipped, &schedule).unwrap();
+				assert_eq!(fallback_position(slot, author, &schedule), Some(skipped));
+			}
+			assert_eq!(fallback_position(slot, 1, &schedule), None);
+		}
+	}
+
+	mod equivocation {
+		use super::*;
+		use polkadot_sdk::{
+			sp_core::{Pair, H256},
+			sp_runtime::{
+				generic::{self, DigestItem},
+				traits::BlakeTwo256,
+			},
+		};
+
+		type Header = generic::Header<u64, BlakeTwo256>;
+
+		fn pair(seed: &str) -> sr25519::AuthorityPair {
+			sr25519::AuthorityPair::from_string(seed, None).unwrap()
+		}
+
+		/// A header claiming `slot`, sealed by `pair`. Headers of different `state_root`s differ.
+		fn sealed_header(pair: &sr25519::AuthorityPair, slot: u64, state_root: u8) -> Header {
+			sealed_child(pair, slot, state_root, H256::zero())
+		}
+
+		/// A header like [`sealed_header`] on the parent `parent_hash`.
+		fn sealed_child(
+			pair: &sr25519::AuthorityPair,
+			slot: u64,
+			state_root: u8,
+			parent_hash: H256,
+		) -> Header {
+			let mut header = Header::new(
+				1,
+				H256::zero(),
+				H256::repeat_byte(state_root),
+				parent_hash,
+				Default::default(),
+			);
+			let pre_digest = PreDigest { authority_index: 0, slot: slot.into(), skipped: 0 };
+			header.digest_mut().push(DigestItem::round_robin_pre_digest(pre_digest));
+			let signature = pair.sign(header.hash().as_ref());
+			header
+				.digest_mut()
+				.push(DigestItem::round_robin_seal(AuthoritySignature::Sr25519(signature)));
+			header
+		}
+
+		fn proof(
+			offender: &sr25519::AuthorityPair,
+			slot: u64,
+			first_header: Header,
+			second_header: Header,
+		) -> EquivocationProof<Header> {
+			EquivocationProof {
+				offender: offender.public().into(),
+				slot: slot.into(),
+				first_header,
+				second_header,
+			}
+		}
+
+		#[test]
+		fn two_blocks_sealed_for_one_slot_are_an_equivocation() {
+			let alice = pair("//Alice");
+			let proof = proof(&alice, 7, sealed_header(&alice, 7, 1), sealed_header(&alice, 7, 2));
+			assert!(check_equivocation_proof(&proof, false));
+		}
+
+		#[test]
+		fn the_same_block_twice_is_no_equivocation() {
+			let alice = pair("//Alice");
+			let header = sealed_header(&alice, 7, 1);
+			assert!(!check_equivocation_proof(&proof(&alice, 7, header.clone(), header), false));
+		}
+
+		#[test]
+		fn blocks_of_different_slots_are_no_equivocation() {
+			let alice = pair("//Alice");
+			let first = sealed_header(&alice, 7, 1);
+			let second = sealed_header(&alice, 8, 2);
+			let wrong_slot = proof(&alice, 7, first.clone(), second.clone());
+			assert!(!check_equivocation_proof(&wrong_slot, false));
+			assert!(!check_equivocation_proof(&proof(&alice, 8, first, second), false));
+		}
+
+		#[test]
+		fn blocks_on_different_parents_are_no_equivocation_by_height() {
+			let alice = pair("//Alice");
+			let first = sealed_child(&alice, 7, 1, H256::repeat_byte(1));
+			let second = sealed_child(&alice, 7, 2, H256::repeat_byte(2));
+			let reauthored = proof(&alice, 7, first, second);
+			assert!(check_equivocation_proof(&reauthored, false));
+			assert!(!check_equivocation_proof(&reauthored, true));
+
+			let siblings =
+				proof(&alice, 7, sealed_header(&alice, 7, 1), sealed_header(&alice, 7, 2));
+			assert!(check_equivocation_proof(&siblings, true));
+		}
+
+		#[test]
+		fn blocks_must_both_be_sealed_by_the_offender() {
+			let (alice, bob) = (pair("//Alice"), pair("//Bob"));
+			let by_bob = proof(&alice, 7, sealed_header(&alice, 7, 1), sealed_header(&bob, 7, 2));
+			assert!(!check_equivocation_proof(&by_bob, false));
+
+			let mut unsealed = sealed_header(&alice, 7, 2);
+			unsealed.digest_mut().pop();
+			let unsealed = proof(&alice, 7, sealed_header(&alice, 7, 1), unsealed);
+			assert!(!check_equivocation_proof(&unsealed, false));
+		}
+	}
+}
@@ -0,0 +1,16 @@
+//! Post-quantum signature schemes for QPOW keys.
+//!
+//! The types here are plain byte wrappers that can be stored on chain and verified inside the
+//! runtime without host functions. Key pairs are only available with the `std` feature.
+//!
+//! [`MultiSignature`] extends the classical transaction signature with ML-DSA-65, so accounts can
+//! be held under quantum-safe keys.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod ml_dsa;
+mod multi;
+
+pub use multi::{MultiSignature, MultiSigner};
@@ -0,0 +1,177 @@
+//! ML-DSA-65 (FIPS 204, formerly CRYSTALS-Dilithium) signatures.
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use core::fmt;
+use fips204::{
+	ml_dsa_65,
+	traits::{SerDes, Verifier},
+};
+use polkadot_sdk::sp_core::hexdisplay::HexDisplay;
+use scale_info::TypeInfo;
+
+/// Length of an encoded ML-DSA-65 public key.
+pub const PUBLIC_KEY_LEN: usize = ml_dsa_65::PK_LEN;
+
+/// Length of an encoded ML-DSA-65 signature.
+pub const SIGNATURE_LEN: usize = ml_dsa_65::SIG_LEN;
+
+/// Context string binding every signature to this chain family.
+const CONTEXT: &[u8] = b"qpow";
+
+/// An ML-DSA-65 public key.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode, MaxEncodedLen, TypeInfo)]
+pub struct Public(pub [u8; PUBLIC_KEY_LEN]);
+
+/// An ML-DSA-65 signature.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo)]
+pub struct Signature(pub [u8; SIGNATURE_LEN]);
+
+impl Public {
+	/// Verifies `signature` over `message` under this key.
+	pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+		match ml_dsa_65::PublicKey::try_from_bytes(self.0) {
+			Ok(public) => public.verify(message, &signature.0, CONTEXT),
+			Err(_) => false,
+		}
+	}
+}
+
+impl AsRef<[u8]> for Public {
+	fn as_ref(&self) -> &[u8] {
+		&self.0[..]
+	}
+}
+
+impl fmt::Debug for Public {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "MlDsa65({}..)", HexDisplay::from(&&self.0[..8]))
+	}
+}
+
+impl fmt::Debug for Signature {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "MlDsa65Signature({}..)", HexDisplay::from(&&self.0[..8]))
+	}
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Public {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		polkadot_sdk::sp_core::bytes::serialize(&self.0[..], serializer)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Public {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let bytes = polkadot_sdk::sp_core::bytes::deserialize(deserializer)?;
+		let key = bytes
+			.try_into()
+			.map_err(|_| serde::de::Error::custom("invalid ML-DSA-65 public key length"))?;
+		Ok(Public(key))
+	}
+}
+
+/// An ML-DSA-65 key pair.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Pair {
+	public: Public,
+	secret: ml_dsa_65::PrivateKey,
+}
+
+#[cfg(feature = "std")]
+impl Pair {
+	/// Deterministically generates a key pair from a 32-byte seed.
+	pub fn from_seed(seed: &[u8; 32]) -> Self {
+		use fips204::traits::KeyGen;
+
+		let (public, secret) = ml_dsa_65::KG::keygen_from_seed(seed);
+		Self { public: Public(public.into_bytes()), secret }
+	}
+
+	/// Generates a key pair from a secret URI.
+	///
+	/// A `0x`-prefixed 32-byte hex string is used as the seed directly; any other string (such as
+	/// `//Alice`) is hashed with BLAKE2-256 into a seed. ML-DSA has no hierarchical derivation, so
+	/// derivation paths are not interpreted.
+	pub fn from_string(suri: &str) -> Result<Self, &'static str> {
+		let seed = match suri.strip_prefix("0x") {
+			Some(hex) => polkadot_sdk::sp_core::bytes::from_hex(hex)
+				.map_err(|_| "invalid hex seed")?
+				.try_into()
+				.map_err(|_| "hex seed must be 32 bytes")?,
+			None => polkadot_sdk::sp_core::hashing::blake2_256(suri.as_bytes()),
+		};
+		Ok(Self::from_seed(&seed))
+	}
+
+	/// The public half of this key pair.
+	pub fn public(&self) -> Public {
+		self.public.clone()
+	}
+
+	/// Signs `message`.
+	pub fn sign(&self, message: &[u8]) -> Signature {
+		use fips204::traits::Signer;
+
+		Signature(self.secret.try_sign(message, CONTEXT).expect("context is under 255 bytes; qed"))
+	}
+
+	/// Verifies `signature` over `message` under `public`.
+	pub fn verify(signature: &Signature, message: &[u8], public: &Public) -> bool {
+		public.verify(message, signature)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_sdk::sp_core::hashing::blake2_256;
+
+	#[test]
+	fn signatures_verify_under_the_signing_key() {
+		let pair = Pair::from_string("//Alice").unwrap();
+		let signature = pair.sign(b"message");
+		assert!(Pair::verify(&signature, b"message", &pair.public()));
+	}
+
+	#[test]
+	fn signatures_fail_under_other_keys_or_messages() {
+		let alice = Pair::from_string("//Alice").unwrap();
+		let bob = Pair::from_string("//Bob").unwrap();
+		let signature = alice.sign(b"message");
+		assert!(!bob.public().verify(b"message", &signature));
+		assert!(!alice.public().verify(b"massage", &signature));
+
+		let mut tampered = signature.clone();
+		tampered.0[0] ^= 1;
+		assert!(!alice.public().verify(b"message", &tampered));
+	}
+
+	#[test]
+	fn secret_uris_give_the_same_key_every_time() {
+		let alice = || Pair::from_string("//Alice").unwrap().public();
+		assert_eq!(alice(), alice());
+		assert_eq!(alice(), Pair::from_seed(&blake2_256(b"//Alice")).public());
+		assert_ne!(alice(), Pair::from_string("//Bob").unwrap().public());
+
+		let hex_seed = Pair::from_string(&format!("0x{}", "07".repeat(32))).unwrap();
+		assert_eq!(hex_seed.public(), Pair::from_seed(&[7; 32]).public());
+		assert!(Pair::from_string("0x07").is_err());
+	}
+
+	#[test]
+	fn boxed_keys_and_signatures_encode_as_unboxed_ones() {
+		let pair = Pair::from_seed(&[1; 32]);
+		let public = Box::new(pair.public());
+		let signature = Box::new(pair.sign(b"message"));
+
+		assert_eq!(public.encode(), pair.public().encode());
+		assert_eq!(public.encode().len(), PUBLIC_KEY_LEN);
+		assert_eq!(Box::<Public>::decode(&mut &public.encode()[..]).unwrap(), public);
+		assert_eq!(signature.encode(), (*signature).encode());
+		assert_eq!(signature.encode().len(), SIGNATURE_LEN);
+		assert_eq!(Box::<Signature>::decode(&mut &signature.encode()[..]).unwrap(), signature);
+	}
+}
@@ -0,0 +1,147 @@
+//! Transaction signatures accepting both classical and post-quantum keys.
+
+use crate::ml_dsa;
+use alloc::boxed::Box;
+use codec::{Decode, Encode};
+use polkadot_sdk::{
+	sp_core::{ecdsa, ed25519, hashing::blake2_256, sr25519},
+	sp_runtime::{
+		traits::{IdentifyAccount, Lazy, Verify},
+		AccountId32,
+	},
+};
+use scale_info::TypeInfo;
+
+/// A transaction signature.
+///
+/// The classical variants keep the indices of [`polkadot_sdk::sp_runtime::MultiSignature`], so
+/// existing signers produce valid encodings. An ML-DSA-65 public key cannot be recovered from a
+/// signature or its account id, so it travels alongside the signature. Both are boxed, keeping
+/// classical signatures small; boxing does not change the encoding.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum MultiSignature {
+	/// An Ed25519 signature.
+	Ed25519(ed25519::Signature),
+	/// An Sr25519 signature.
+	Sr25519(sr25519::Signature),
+	/// An ECDSA/SECP256k1 signature.
+	Ecdsa(ecdsa::Signature),
+	/// An ML-DSA-65 signature together with the signer's public key.
+	MlDsa65(Box<ml_dsa::Public>, Box<ml_dsa::Signature>),
+}
+
+/// The public key of a transaction signer.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum MultiSigner {
+	/// An Ed25519 identity.
+	Ed25519(ed25519::Public),
+	/// An Sr25519 identity.
+	Sr25519(sr25519::Public),
+	/// An ECDSA/SECP256k1 identity; the account is the BLAKE2-256 hash of the compressed key.
+	Ecdsa(ecdsa::Public),
+	/// An ML-DSA-65 identity; the account is the BLAKE2-256 hash of the public key. Boxed, as for
+	/// [`MultiSignature`].
+	MlDsa65(Box<ml_dsa::Public>),
+}
+
+impl Verify for MultiSignature {
+	type Signer = MultiSigner;
+
+	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &AccountId32) -> bool {
+		use polkadot_sdk::sp_runtime::MultiSignature as Classical;
+
+		match self {
+			Self::Ed25519(sig) => Classical::Ed25519(*sig).verify(msg, signer),
+			Self::Sr25519(sig) => Classical::Sr25519(*sig).verify(msg, signer),
+			Self::Ecdsa(sig) => Classical::Ecdsa(*sig).verify(msg, signer),
+			Self::MlDsa65(public, sig) =>
+				MultiSigner::MlDsa65(public.clone()).into_account() == *signer &&
+					public.verify(msg.get(), sig),
+		}
+	}
+}
+
+impl IdentifyAccount for MultiSigner {
+	type AccountId = AccountId32;
+
+	fn into_account(self) -> AccountId32 {
+		match self {
+			Self::Ed25519(who) => <[u8; 32]>::from(who).into(),
+			Self::Sr25519(who) => <[u8; 32]>::from(who).into(),
+			Self::Ecdsa(who) => blake2_256(who.as_ref()).into(),
+			Self::MlDsa65(who) => blake2_256(&who.0).into(),
+		}
+	}
+}
+
+impl From<polkadot_sdk::sp_runtime::MultiSignature> for MultiSignature {
+	fn from(signature: polkadot_sdk::sp_runtime::MultiSignature) -> Self {
+		use polkadot_sdk::sp_runtime::MultiSignature as Classical;
+
+		match signature {
+			Classical::Ed25519(sig) => Self::Ed25519(sig),
+			Classical::Sr25519(sig) => Self::Sr25519(sig),
+			Classical::Ecdsa(sig) => Self::Ecdsa(sig),
+		}
+	}
+}
+
+impl From<ml_dsa::Public> for MultiSigner {
+	fn from(public: ml_dsa::Public) -> Self {
+		Self::MlDsa65(Box::new(public))
+	}
+}
+
+impl From<sr25519::Public> for MultiSigner {
+	fn from(public: sr25519::Public) -> Self {
+		Self::Sr25519(public)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_sdk::sp_core::Pair as _;
+
+	#[test]
+	fn classical_signatures_keep_their_encoding() {
+		use polkadot_sdk::sp_runtime::MultiSignature as Classical;
+
+		let classical = [
+			Classical::Ed25519(ed25519::Pair::from_seed(&[1; 32]).sign(b"payload")),
+			Classical::Sr25519(sr25519::Pair::from_seed(&[1; 32]).sign(b"payload")),
+			Classical::Ecdsa(ecdsa::Pair::from_seed(&[1; 32]).sign(b"payload")),
+		];
+		for (index, signature) in classical.into_iter().enumerate() {
+			let encoded = signature.encode();
+			assert_eq!(encoded[0], index as u8);
+			assert_eq!(MultiSignature::from(signature).encode(), encoded);
+		}
+	}
+
+	#[test]
+	fn ml_dsa_signatures_verify_against_the_hashed_key_account() {
+		let pair = ml_dsa::Pair::from_seed(&[1; 32]);
+		let signature =
+			MultiSignature::MlDsa65(Box::new(pair.public()), Box::new(pair.sign(b"payload")));
+		let account = AccountId32::from(blake2_256(&pair.public().0));
+
+		assert_eq!(MultiSigner::from(pair.public()).into_account(), account);
+		assert!(signature.verify(&b"payload"[..], &account));
+		assert!(!signature.verify(&b"other payload"[..], &account));
+	}
+
+	#[test]
+	fn ml_dsa_signatures_fail_against_other_accounts() {
+		let alice = ml_dsa::Pair::from_seed(&[1; 32]);
+		let bob = ml_dsa::Pair::from_seed(&[2; 32]);
+		let signature =
+			MultiSignature::MlDsa65(Box::new(alice.public()), Box::new(alice.sign(b"payload")));
+		assert!(!signature.verify(&b"payload"[..], &MultiSigner::from(bob.public()).into_account()));
+
+		// Claiming bob's key does not make alice's signature his
+		let forged =
+			MultiSignature::MlDsa65(Box::new(bob.public()), Box::new(alice.sign(b"payload")));
+		assert!(!forged.verify(&b"payload"[..], &MultiSigner::from(bob.public()).into_account()));
+	}
+}
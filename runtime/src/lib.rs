@@ -26,13 +26,16 @@ include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 extern crate alloc;
 
 use alloc::{vec, vec::Vec};
+use codec::Decode;
 use pallet_transaction_payment::{FeeDetails, RuntimeDispatchInfo};
 use polkadot_sdk::{
+	frame_system::EnsureRoot,
 	polkadot_sdk_frame::{
-		self as frame,
 		prelude::*,
 		runtime::{apis, prelude::*},
+		traits::{Defensive, FindAuthor},
 	},
+	sp_runtime::{traits::IdentifyAccount, ConsensusEngineId},
 	*,
 };
 
@@ -42,10 +45,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("minimal-template-runtime"),
 	impl_name: create_runtime_str!("minimal-template-runtime"),
 	authoring_version: 1,
-	spec_version: 0,
+	spec_version: 1,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 	state_version: 1,
 };
 
@@ -117,10 +120,30 @@ mod runtime {
 	/// A minimal pallet template.
 	#[runtime::pallet_index(5)]
 	pub type Template = pallet_minimal_template::Pallet<Runtime>;
+
+	/// Stores the authorities taking turns in round-robin block authoring.
+	#[runtime::pallet_index(6)]
+	pub type RoundRobin = pallet_round_robin::Pallet<Runtime>;
+
+	/// Stores and retargets the proof-of-work difficulty.
+	#[runtime::pallet_index(7)]
+	pub type Difficulty = pallet_difficulty::Pallet<Runtime>;
+
+	/// Pays block subsidies and transaction fees to block authors.
+	#[runtime::pallet_index(8)]
+	pub type Rewards = pallet_rewards::Pallet<Runtime>;
+
+	/// Finalizes round-robin blocks by GRANDPA voting among its authorities.
+	///
+	/// After `RoundRobin`, so the voter changes it schedules on finalize are signalled and enacted
+	/// in the same block.
+	#[runtime::pallet_index(9)]
+	pub type Grandpa = pallet_grandpa::Pallet<Runtime>;
 }
 
 parameter_types! {
 	pub const Version: RuntimeVersion = VERSION;
+	pub const InitialSubsidy: interface::Balance = 50;
 }
 
 /// Implements the types required for the system pallet.
@@ -142,24 +165,154 @@ impl pallet_balances::Config for Runtime {
 #[derive_impl(pallet_sudo::config_preludes::TestDefaultConfig)]
 impl pallet_sudo::Config for Runtime {}
 
-// Implements the types required for the sudo pallet.
+// Implements the types required for the timestamp pallet.
 #[derive_impl(pallet_timestamp::config_preludes::TestDefaultConfig)]
-impl pallet_timestamp::Config for Runtime {}
+impl pallet_timestamp::Config for Runtime {
+	// Checks that round-robin blocks claim the slot of their timestamp
+	type OnTimestampSet = RoundRobin;
+	type MinimumPeriod = MinimumPeriod;
+}
+
+/// The minimum period between the timestamps of a block and its parent, in milliseconds.
+///
+/// Half the round-robin slot duration or, with turns by height, half the block time. Read from
+/// storage, so the value in the metadata is that of the chain it was read from.
+pub struct MinimumPeriod;
+
+impl Get<u64> for MinimumPeriod {
+	fn get() -> u64 {
+		RoundRobin::minimum_period()
+	}
+}
 
 // Implements the types required for the transaction payment pallet.
 #[derive_impl(pallet_transaction_payment::config_preludes::TestDefaultConfig)]
 impl pallet_transaction_payment::Config for Runtime {
-	type OnChargeTransaction = pallet_transaction_payment::FungibleAdapter<Balances, ()>;
+	// Pay fees to the block author
+	type OnChargeTransaction = pallet_transaction_payment::FungibleAdapter<Balances, Rewards>;
 	// Setting fee as independent of the weight of the extrinsic for demo purposes
 	type WeightToFee = NoFee<<Self as pallet_balances::Config>::Balance>;
-	// Setting fee as fixed for any length of the call data for demo purposes
-	type LengthToFee = FixedFee<1, <Self as pallet_balances::Config>::Balance>;
+	// Charging one unit per byte, so transactions carrying ~5 KiB post-quantum signatures and
+	// keys pay for their size and for their costlier verification
+	type LengthToFee = weights::IdentityFee<<Self as pallet_balances::Config>::Balance>;
 }
 
 // Implements the types required for the template pallet.
 impl pallet_minimal_template::Config for Runtime {}
 
-type Block = frame::runtime::types_common::BlockOf<Runtime, SignedExtra>;
+// Implements the types required for the round-robin pallet.
+impl pallet_round_robin::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type AuthorityOrigin = EnsureRoot<interface::AccountId>;
+	type MaxAuthorities = ConstU32<32>;
+	type MaxScheduleLength = ConstU32<1024>;
+	// As long as nodes keep the headers equivocations are detected with
+	type ReportLongevity = ConstU64<1000>;
+	type FinalityId = sp_consensus_grandpa::AuthorityId;
+	type OnAuthoritiesChanged = FollowRoundRobinAuthorities;
+}
+
+/// Moves the GRANDPA voters to the finality keys of the round-robin authority set whenever it
+/// changes, each with one vote. The change takes effect once the block making it is finalized by
+/// the previous voters.
+pub struct FollowRoundRobinAuthorities;
+
+impl pallet_round_robin::OnAuthoritiesChanged<sp_consensus_grandpa::AuthorityId>
+	for FollowRoundRobinAuthorities
+{
+	fn on_authorities_changed(finality_keys: Vec<sp_consensus_grandpa::AuthorityId>) {
+		let voters: sp_consensus_grandpa::AuthorityList =
+			finality_keys.into_iter().map(|key| (key, 1)).collect();
+		// Without voters no block could be finalized again
+		if voters.is_empty() || voters == Grandpa::grandpa_authorities() {
+			return;
+		}
+		// No change is pending: only this schedules them, at most once per block, and changes
+		// without delay are enacted in the block scheduling them
+		let _ = Grandpa::schedule_change(voters, 0, None).defensive();
+	}
+}
+
+// Lets pallets submit unsigned transactions from the node, such as equivocation reports.
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime
+where
+	RuntimeCall: From<C>,
+{
+	type Extrinsic = UncheckedExtrinsic;
+	type OverarchingCall = RuntimeCall;
+}
+
+/// Identifies block authors from the pre-runtime digest of round-robin or proof-of-work blocks.
+///
+/// Round-robin blocks name the index of their author in the authority set, whose account is the one
+/// controlled by the authority key. Proof-of-work blocks carry the encoded account of their miner.
+pub struct BlockAuthor;
+
+impl FindAuthor<interface::AccountId> for BlockAuthor {
+	fn find_author<'a, I>(digests: I) -> Option<interface::AccountId>
+	where
+		I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+	{
+		let digests = digests.into_iter().collect::<Vec<_>>();
+		if let Some(authority) = RoundRobin::find_author(digests.iter().copied()) {
+			return Some(authority.into_account());
+		}
+
+		digests.into_iter().find_map(|(id, mut data)| {
+			(id == sp_consensus_pow::POW_ENGINE_ID)
+				.then(|| interface::AccountId::decode(&mut data).ok())
+				.flatten()
+		})
+	}
+}
+
+// Implements the types required for the rewards pallet.
+impl pallet_rewards::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type FindAuthor = BlockAuthor;
+	type InitialSubsidy = InitialSubsidy;
+	type HalvingInterval = ConstU32<210_000>;
+}
+
+// Implements the types required for the difficulty pallet.
+impl pallet_difficulty::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+}
+
+// Implements the types required for the GRANDPA pallet, without equivocation reporting.
+impl pallet_grandpa::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type WeightInfo = ();
+	type MaxAuthorities = ConstU32<32>;
+	type MaxNominators = ConstU32<0>;
+	type MaxSetIdSessionEntries = ConstU64<0>;
+	type KeyOwnerProof = sp_core::Void;
+	type EquivocationReportSystem = ();
+}
+
+sp_runtime::impl_opaque_keys! {
+	/// The session keys of a validator.
+	pub struct SessionKeys {
+		pub round_robin: RoundRobin,
+		pub grandpa: Grandpa,
+	}
+}
+
+/// Transaction signature, accepting ML-DSA-65 alongside the classical schemes.
+pub type Signature = sp_pq_crypto::MultiSignature;
+
+type UncheckedExtrinsic = sp_runtime::generic::UncheckedExtrinsic<
+	sp_runtime::MultiAddress<interface::AccountId, ()>,
+	RuntimeCall,
+	Signature,
+	SignedExtra,
+>;
+
+type Block = sp_runtime::generic::Block<
+	sp_runtime::generic::Header<u32, sp_runtime::traits::BlakeTwo256>,
+	UncheckedExtrinsic,
+>;
 type Header = HeaderFor<Runtime>;
 
 type RuntimeExecutive =
@@ -231,14 +384,93 @@ impl_runtime_apis! {
 	}
 
 	impl apis::SessionKeys<Block> for Runtime {
-		fn generate_session_keys(_seed: Option<Vec<u8>>) -> Vec<u8> {
-			Default::default()
+		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
+			// The keystore only generates sr25519 round-robin keys, which cannot seal blocks here,
+			// so none are generated rather than trapping
+			if RoundRobin::seal_scheme() == Some(sp_consensus_round_robin::SealScheme::MlDsa65) {
+				return Vec::new();
+			}
+			SessionKeys::generate(seed)
 		}
 
 		fn decode_session_keys(
-			_encoded: Vec<u8>,
+			encoded: Vec<u8>,
 		) -> Option<Vec<(Vec<u8>, apis::KeyTypeId)>> {
-			Default::default()
+			SessionKeys::decode_into_raw_public_keys(&encoded)
+		}
+	}
+
+	impl sp_consensus_round_robin::RoundRobinApi<Block> for Runtime {
+		fn authorities() -> Vec<sp_consensus_round_robin::AuthorityId> {
+			RoundRobin::authorities()
+		}
+
+		fn slot_duration() -> Option<sp_consensus_round_robin::SlotDuration> {
+			RoundRobin::slot_duration()
+		}
+
+		fn schedule(
+			slot: sp_consensus_round_robin::Slot,
+		) -> Vec<sp_consensus_round_robin::AuthorityIndex> {
+			RoundRobin::schedule(slot)
+		}
+
+		fn fallback_timestamp(skipped: u32) -> Option<u64> {
+			RoundRobin::fallback_timestamp(skipped)
+		}
+
+		fn block_time() -> Option<u64> {
+			RoundRobin::block_time()
+		}
+
+		fn missed_turn_timeout() -> Option<u64> {
+			RoundRobin::missed_turn_timeout()
+		}
+
+		fn block_timestamp(extrinsics: &Vec<UncheckedExtrinsic>) -> Option<u64> {
+			extrinsics.iter().find_map(|extrinsic| match extrinsic.function {
+				RuntimeCall::Timestamp(pallet_timestamp::Call::set { now }) => Some(now),
+				_ => None,
+			})
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			equivocation_proof: sp_consensus_round_robin::EquivocationProof<Header>,
+		) -> Option<()> {
+			RoundRobin::submit_unsigned_equivocation_report(equivocation_proof)
+		}
+	}
+
+	impl sp_consensus_pow::DifficultyApi<Block, sp_core::U256> for Runtime {
+		fn difficulty() -> sp_core::U256 {
+			Difficulty::difficulty()
+		}
+	}
+
+	impl sp_consensus_grandpa::GrandpaApi<Block> for Runtime {
+		fn grandpa_authorities() -> sp_consensus_grandpa::AuthorityList {
+			Grandpa::grandpa_authorities()
+		}
+
+		fn current_set_id() -> sp_consensus_grandpa::SetId {
+			Grandpa::current_set_id()
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			_equivocation_proof: sp_consensus_grandpa::EquivocationProof<
+				<Runtime as frame_system::Config>::Hash,
+				BlockNumberFor<Runtime>,
+			>,
+			_key_owner_proof: sp_consensus_grandpa::OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			None
+		}
+
+		fn generate_key_ownership_proof(
+			_set_id: sp_consensus_grandpa::SetId,
+			_authority_id: sp_consensus_grandpa::AuthorityId,
+		) -> Option<sp_consensus_grandpa::OpaqueKeyOwnershipProof> {
+			None
 		}
 	}
 